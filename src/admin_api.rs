@@ -1,45 +1,103 @@
+use crate::auth::{
+    authenticate_api_key, authenticate_password, format_key, generate_key_id, generate_secret,
+    hash_secret, issue_tokens, refresh_tokens, revoke_tokens, verify_api_key, verify_bearer_token,
+};
+use crate::bulk::{content_type, export_games, parse_games};
 use crate::db::DB;
-use crate::dto::{BaseResponse, BaseStringDTO, GameDBDTO, GameDTO, Override};
+use crate::dto::{
+    ActiveGameDTO, ApiKeyCreatedDTO, ApiKeyDTO, ApiKeyInfoDTO, BaseResponse, BaseStringDTO,
+    CreateApiKeyDTO, DiffQuery, ExportQuery, GameDTO, GameDiffDTO, GameListQuery, GameMetadataDTO,
+    GameUpdateDTO, GameVersionDTO, GenerateGameDTO, GeneratedGameDTO, ImportQuery, ImportReportDTO,
+    ImportRowDTO, KickDTO, LobbySettingsDTO, LoginDTO, ModerationMode, Override, RandomGameDTO,
+    RefreshDTO, RestoreDTO, ReviewActionDTO, ReviewGapDTO, ReviewStatus, SessionDTO, StartGameDTO,
+    StartGameResponse, TemplateQuery, TokenResponseDTO, ValidationErrorDTO, ValidationResponse,
+    WebsocketMetricsDTO, WordListDTO,
+};
+use crate::game::{remove_user, schedule_fill_time_limit, start_guessing};
+use crate::moderation::{cache_word_list, load_word_lists};
 use crate::objects::Lobby;
-use crate::utils::id_generator;
-use crate::ws_dto::WSMessage;
-use axum::extract::{Query, Request, State};
-use axum::http::StatusCode;
+use crate::rate_limit::client_ip;
+use crate::sanitize::sanitize_text;
+use crate::template::{
+    expand_placeholders, generate_sections, BlankMode, DEFAULT_EVERY, DEFAULT_MARKER,
+};
+use crate::utils::{diff_sections, id_generator, unix_timestamp};
+use crate::validation::{validate_api_key, validate_game, validate_settings, validate_word_list};
+use crate::websocket;
+use crate::ws_dto::ServerMessage;
+use axum::extract::{ConnectInfo, Path, Query, Request, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
 use axum::middleware::Next;
 use axum::response::{IntoResponse, Response};
 use axum::Json;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
 use lazy_static::lazy_static;
-use serde_json::to_string;
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::string::ToString;
 use std::sync::{Arc, RwLock};
 use surrealdb::engine::remote::ws::Client;
 use surrealdb::Surreal;
+use tracing::{event, Level};
 
 type SharedAppState = Arc<RwLock<HashMap<String, RwLock<Lobby>>>>;
 
 lazy_static! {
-    static ref ENV: bool = std::env::var("PRODUCTION")
-        .unwrap_or("false".to_string())
-        .parse()
-        .unwrap();
-    static ref AUTH_KEY: String =
-        std::env::var("AUTH_KEY").unwrap_or_else(|_| "Blank1".to_string());
-}
-
-// Simple middleware to check if the api key is correct
-pub async fn auth_check(request: Request, next: Next) -> Response {
-    if *ENV {
-        match request.headers().get("apikey") {
-            Some(key) if key != AUTH_KEY.as_str() => {
-                return (
-                    StatusCode::UNAUTHORIZED,
-                    Json(BaseResponse {
-                        success: false,
-                        message: Some("Unauthorized".to_string()),
-                    }),
-                )
-                    .into_response();
+    // Skips the authentication of admin endpoints, only meant for local development
+    static ref AUTH_DISABLED: bool = std::env::var("AUTH_DISABLED")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(false);
+}
+
+// Middleware to check if the api key is valid and has the scope of the route group
+pub async fn auth_check(
+    State(scope): State<&'static str>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !*AUTH_DISABLED {
+        let bearer = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(|token| token.to_string());
+        let key = request
+            .headers()
+            .get("apikey")
+            .and_then(|key| key.to_str().ok())
+            .map(|key| key.to_string());
+        let verified = match (bearer, key) {
+            (Some(token), _) => Some(verify_bearer_token(&token, scope).await),
+            (None, Some(key)) => {
+                let ip = request
+                    .extensions()
+                    .get::<ConnectInfo<SocketAddr>>()
+                    .map(|ConnectInfo(addr)| client_ip(request.headers(), *addr));
+                match ip {
+                    Some(ip) => Some(verify_api_key(&key, ip, scope).await),
+                    None => Some(Err((
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "Failed to check api key".to_string(),
+                    ))),
+                }
+            }
+            (None, None) => None,
+        };
+        match verified {
+            Some(result) => {
+                if let Err((status, message)) = result {
+                    return (
+                        status,
+                        Json(BaseResponse {
+                            success: false,
+                            message: Some(message),
+                        }),
+                    )
+                        .into_response();
+                }
             }
             None => {
                 return (
@@ -51,256 +109,587 @@ pub async fn auth_check(request: Request, next: Next) -> Response {
                 )
                     .into_response();
             }
-            _ => (),
         }
     }
 
     next.run(request).await
 }
 
+// Url: /api/admin/login
+// Exchanges the admin password or an api key for a short-lived bearer token
+// Method: POST
+// Request: LoginDTO{password: Option<String>, api_key: Option<String>}
+// Response: TokenResponseDTO
+#[utoipa::path(
+    post,
+    path = "/api/admin/login",
+    request_body = LoginDTO,
+    responses(
+        (status = 200, description = "Logged in", body = TokenResponseDTO),
+        (status = 400, description = "Neither password nor api key", body = BaseResponse),
+        (status = 401, description = "Unauthorized", body = BaseResponse),
+        (status = 429, description = "Too many failed logins", body = BaseResponse)
+    ),
+    description = "Exchanges the admin password or an api key for an access token, which is \
+    accepted as `Authorization: Bearer <token>` header instead of the `apikey` header, and a \
+    refresh token. Tokens have the scopes of the api key, or all scopes for the password."
+)]
+pub async fn login_handler(
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(payload): Json<LoginDTO>,
+) -> impl IntoResponse {
+    let identity = match (&payload.password, &payload.api_key) {
+        (Some(password), _) => authenticate_password(password, client_ip(&headers, addr)),
+        (None, Some(key)) => authenticate_api_key(key, client_ip(&headers, addr)).await,
+        (None, None) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("Either a password or an api key is required".to_string()),
+                })
+                .into_response(),
+            );
+        }
+    };
+    match identity {
+        Ok(identity) => (StatusCode::OK, Json(issue_tokens(identity)).into_response()),
+        Err((status, message)) => (
+            status,
+            Json(BaseResponse {
+                success: false,
+                message: Some(message),
+            })
+            .into_response(),
+        ),
+    }
+}
+
+// Url: /api/admin/refresh
+// Exchanges a refresh token for new tokens
+// Method: POST
+// Request: RefreshDTO{refresh_token: String}
+// Response: TokenResponseDTO
+#[utoipa::path(
+    post,
+    path = "/api/admin/refresh",
+    request_body = RefreshDTO,
+    responses(
+        (status = 200, description = "Tokens refreshed", body = TokenResponseDTO),
+        (status = 401, description = "Invalid or expired token, or api key revoked", body = BaseResponse)
+    )
+)]
+pub async fn refresh_handler(Json(payload): Json<RefreshDTO>) -> impl IntoResponse {
+    match refresh_tokens(&payload.refresh_token).await {
+        Ok(tokens) => (StatusCode::OK, Json(tokens).into_response()),
+        Err((status, message)) => (
+            status,
+            Json(BaseResponse {
+                success: false,
+                message: Some(message),
+            })
+            .into_response(),
+        ),
+    }
+}
+
+// Tags are matched case-insensitively, so they are stored trimmed and in lower case
+fn normalize_metadata(metadata: GameMetadataDTO) -> GameMetadataDTO {
+    let mut tags: Vec<String> = metadata
+        .tags
+        .iter()
+        .map(|t| t.trim().to_lowercase())
+        .collect();
+    tags.sort();
+    tags.dedup();
+    GameMetadataDTO { tags, ..metadata }
+}
+
+// Stores a new version of the template in the `game_version` history and as latest version in
+// the `game` table, returns the stored version
+async fn save_game_version(
+    con: &Surreal<Client>,
+    game: GameDTO,
+    previous: Option<GameVersionDTO>,
+) -> surrealdb::Result<GameVersionDTO> {
+    let now = unix_timestamp();
+    let created_at = previous
+        .as_ref()
+        .map(|p| p.created_at)
+        .filter(|&c| c > 0)
+        .unwrap_or(now);
+    let (version, legacy) = match previous {
+        None => (1, None),
+        Some(previous) if previous.version == 0 => {
+            // template saved before versioning, keep its text as first version
+            let legacy = GameVersionDTO {
+                version: 1,
+                ..previous
+            };
+            (2, Some(legacy))
+        }
+        Some(previous) => (previous.version + 1, None),
+    };
+    let record = GameVersionDTO {
+        name: game.name.clone(),
+        version,
+        text_section: game.text_section,
+        hints: game.hints.iter().map(|h| h.trim().to_string()).collect(),
+        constraints: game.constraints,
+        metadata: normalize_metadata(game.metadata),
+        created_at,
+        updated_at: now,
+    };
+    // the version and the latest template are written together, a concurrent save of the same
+    // version fails on the unique index of game_version
+    con.query(
+        "BEGIN TRANSACTION;
+        IF $legacy { CREATE game_version CONTENT $legacy; };
+        CREATE game_version CONTENT $record;
+        UPSERT type::thing('game', $name) CONTENT $record;
+        COMMIT TRANSACTION;",
+    )
+    .bind(("legacy", legacy))
+    .bind(("record", record.clone()))
+    .bind(("name", game.name))
+    .await?
+    .check()?;
+    Ok(record)
+}
+
+// Loads a specific version of a template from the history
+async fn load_game_version(
+    con: &Surreal<Client>,
+    name: String,
+    version: u32,
+) -> surrealdb::Result<Option<GameVersionDTO>> {
+    let mut response = con
+        .query("SELECT * FROM game_version WHERE name = $name AND version = $version LIMIT 1")
+        .bind(("name", name))
+        .bind(("version", version))
+        .await?;
+    response.take(0)
+}
+
+// Loads all versions of a template from the history, oldest first
+async fn load_game_versions(
+    con: &Surreal<Client>,
+    name: String,
+) -> surrealdb::Result<Vec<GameVersionDTO>> {
+    let mut response = con
+        .query("SELECT * FROM game_version WHERE name = $name ORDER BY version ASC")
+        .bind(("name", name))
+        .await?;
+    response.take(0)
+}
+
 // Url: /api/admin/new
-// Saves a new game to the surrealdb
-// Method:OST
+// Saves a new game to the surrealdb, an existing game is only updated with a new version if forced
+// Method: POST
 // Request: GameDTO{id: String, gaps: Vec<String>}
 // Response: BaseResponse
 #[utoipa::path(
     post,
     path = "/api/admin/new",
     params(
-        ("force" = Option<bool>, Query, description = "Force update if game already exists")
+        ("force" = Option<bool>, Query, description = "Save as new version if game already exists")
     ),
     security(
-        ("ApiKey" = [])
+        ("ApiKey" = []),
+        ("Bearer" = [])
     ),
     request_body = GameDTO,
     responses(
         (status = 200, description = "Game saved", body = BaseResponse),
         (status = 409, description = "Game already exists", body = BaseResponse),
+        (status = 422, description = "Invalid game template", body = ValidationResponse),
         (status = 500, description = "Failed to check if game exists", body = BaseResponse),
-        (status = 500, description = "Failed to delete game to update existing game", body = BaseResponse),
         (status = 500, description = "Failed to insert game", body = BaseResponse)
     )
 )]
 pub async fn new_game_handler(
-    State(state): State<SharedAppState>,
+    State(_state): State<SharedAppState>,
     Query(force): Query<Override>,
     Json(payload): Json<GameDTO>,
 ) -> impl IntoResponse {
-    // Save the new game to the SurrealDB here
-    let con: &Surreal<Client> = DB.get().await;
-
-    let exists: surrealdb::Result<Option<GameDBDTO>> =
-        con.select(("game", payload.name.clone())).await;
-
-    if exists.is_ok() && exists.as_ref().unwrap().is_some() {
-        if force.force.unwrap_or(false) {
-            let delete_response: surrealdb::Result<Option<GameDBDTO>> =
-                con.delete(("game", payload.name.clone())).await;
-            if delete_response.is_err() {
-                return (
-                    StatusCode::INTERNAL_SERVER_ERROR,
-                    Json(BaseResponse {
-                        success: false,
-                        message: Some("Failed to delete game to update existing game".to_string()),
-                    }),
-                );
-            }
-        } else {
-            return (
-                StatusCode::CONFLICT,
-                Json(BaseResponse {
-                    success: false,
-                    message: Some("Game already exists".to_string()),
-                }),
-            );
-        }
-    } else {
-        if exists.is_err() {
+    // Reject invalid templates before touching the database
+    let payload = match expand_placeholders(payload) {
+        Ok(payload) => payload,
+        Err(errors) => {
             return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(BaseResponse {
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(ValidationResponse {
                     success: false,
-                    message: Some("Failed to check if game exists".to_string()),
-                }),
+                    message: Some("Invalid game template".to_string()),
+                    errors,
+                })
+                .into_response(),
             );
         }
+    };
+    let errors = validate_game(&payload);
+    if !errors.is_empty() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ValidationResponse {
+                success: false,
+                message: Some("Invalid game template".to_string()),
+                errors,
+            })
+            .into_response(),
+        );
     }
 
-    let insert_response: surrealdb::Result<Option<GameDBDTO>> = con
-        .insert(("game", payload.name.clone()))
-        .content(payload)
-        .await;
-    if insert_response.is_err() {
+    // Save the new game to the SurrealDB here
+    let con: &Surreal<Client> = DB.get().await;
+
+    let exists: surrealdb::Result<Option<GameVersionDTO>> =
+        con.select(("game", payload.name.clone())).await;
+    if exists.is_err() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Failed to check if game exists".to_string()),
+            })
+            .into_response(),
+        );
+    }
+    let previous = exists.unwrap();
+    if previous.is_some() && !force.force.unwrap_or(false) {
+        return (
+            StatusCode::CONFLICT,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Game already exists".to_string()),
+            })
+            .into_response(),
+        );
+    }
+
+    let saved = save_game_version(con, payload, previous).await;
+    if saved.is_err() {
         return (
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(BaseResponse {
                 success: false,
                 message: Some("Failed to insert game".to_string()),
-            }),
+            })
+            .into_response(),
         );
     }
     (
         StatusCode::OK,
         Json(BaseResponse {
             success: true,
-            message: Some("Game saved".to_string()),
-        }),
+            message: Some(format!("Game saved as version {}", saved.unwrap().version)),
+        })
+        .into_response(),
     )
 }
 
 // Url: /api/admin/available
-// Returns the available games
+// Returns the latest version of the available games, filtered, sorted and paginated
 // Method: GET
-// Response: Vec<Games{id: String, gaps: Vec<String>}>
+// Response: Vec<GameVersionDTO>, the cursor of the next page is returned in the x-next-cursor header
 #[utoipa::path(
     get,
     path = "/api/admin/available",
+    params(
+        ("tag" = Option<String>, Query, description = "Only games with this tag"),
+        ("language" = Option<String>, Query, description = "Only games in this language"),
+        ("sort" = Option<String>, Query, description = "Sort by name (default), created or updated"),
+        ("order" = Option<String>, Query, description = "asc (default) or desc"),
+        ("cursor" = Option<String>, Query, description = "Cursor returned with the previous page"),
+        ("limit" = Option<usize>, Query, description = "Page size, at most 100 (default 20)")
+    ),
     security(
-        ("ApiKey" = [])
+        ("ApiKey" = []),
+        ("Bearer" = [])
     ),
     responses(
-        (status = 200, description = "Available games retrieved successfully", body = [GameDTO]),
+        (status = 200, description = "Available games retrieved successfully", body = [GameVersionDTO],
+            headers(("x-next-cursor" = String, description = "Cursor of the next page, missing on the last page"))),
+        (status = 400, description = "Invalid sort, order or cursor", body = BaseResponse),
         (status = 500, description = "Failed to get games", body = BaseResponse)
     )
 )]
-pub async fn available_games_handler(State(state): State<SharedAppState>) -> impl IntoResponse {
-    // Get the available games from the SurrealDB here
-    let con: &Surreal<Client> = DB.get().await;
-    let games: surrealdb::Result<Vec<GameDTO>> = con.select("game").await;
-    if games.is_err() {
-        return (
-            StatusCode::INTERNAL_SERVER_ERROR,
+pub async fn available_games_handler(
+    State(_state): State<SharedAppState>,
+    Query(query): Query<GameListQuery>,
+) -> impl IntoResponse {
+    let bad_request = |message: &str| {
+        (
+            StatusCode::BAD_REQUEST,
             Json(BaseResponse {
                 success: false,
-                message: Some("Failed to get games".to_string()),
+                message: Some(message.to_string()),
             })
             .into_response(),
-        );
-    }
-    (
-        StatusCode::OK,
-        Json(
-            games
-                .unwrap()
-                .iter()
-                .map(|game| GameDTO {
-                    name: game.name.clone(),
-                    text_section: game.text_section.clone(),
-                })
-                .collect::<Vec<GameDTO>>(),
         )
-        .into_response(),
-    )
-}
+    };
+    let field = match query.sort.as_deref().unwrap_or("name") {
+        "name" => "name",
+        "created" => "created_at",
+        "updated" => "updated_at",
+        _ => return bad_request("Sort must be one of name, created or updated"),
+    };
+    let (order, after) = match query.order.as_deref().unwrap_or("asc") {
+        "asc" => ("ASC", ">"),
+        "desc" => ("DESC", "<"),
+        _ => return bad_request("Order must be asc or desc"),
+    };
+    let cursor = match query.cursor.as_deref().map(decode_cursor) {
+        Some(Some(cursor)) => Some(cursor),
+        Some(None) => return bad_request("Invalid cursor"),
+        None => None,
+    };
 
-// Url: /api/admin/start
-// Starts a game with the specified id and loads it from db to a temporary game state in memory and
-// create a random short id for the game
-// Method: POST
-// Request: BaseStringDTO{id: String}
-// Response: BaseStringDTO{id: String}
-#[utoipa::path(
-    post,
-    path = "/api/admin/start",
-    security(
-        ("ApiKey" = [])
-    ),
-    request_body = BaseStringDTO,
-    responses(
-        (status = 200, description = "Game started", body = BaseStringDTO),
-        (status = 404, description = "No game found", body = BaseResponse)
-    )
-)]
-pub async fn start_game_handler(
-    State(state): State<SharedAppState>,
-    Json(payload): Json<BaseStringDTO>,
-) -> impl IntoResponse {
-    // Load the game from the SurrealDB and create a temporary game state here
+    // filter, sort and page in the db, the page starts after the sort value and name of the
+    // cursor, so the name is the tie-breaker and pages stay stable when games are added
+    let mut conditions = vec![];
+    if query.tag.is_some() {
+        conditions.push("$tag IN tags".to_string());
+    }
+    if query.language.is_some() {
+        conditions.push("string::lowercase(language ?? '') = $language".to_string());
+    }
+    if cursor.is_some() {
+        conditions.push(match field {
+            "name" => format!("name {} $cursor_name", after),
+            field => format!(
+                "[{} ?? 0, name] {} [$cursor_value, $cursor_name]",
+                field, after
+            ),
+        });
+    }
+    let filter = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+    let order_by = match field {
+        "name" => format!("name {}", order),
+        field => format!("{} {}, name {}", field, order, order),
+    };
+    let limit = query.limit.unwrap_or(20).clamp(1, 100);
+    let (cursor_value, cursor_name) = cursor.unwrap_or_default();
     let con: &Surreal<Client> = DB.get().await;
-    let game_optional: surrealdb::Result<Option<GameDBDTO>> =
-        con.select(("game", payload.name.clone())).await;
-    if game_optional.is_err() || game_optional.as_ref().unwrap().is_none() {
+    // one more game than the page size tells whether there is a next page
+    let games: surrealdb::Result<Vec<GameVersionDTO>> = async {
+        con.query(format!(
+            "SELECT * FROM game {} ORDER BY {} LIMIT $limit",
+            filter, order_by
+        ))
+        .bind(("tag", query.tag.map(|t| t.trim().to_lowercase())))
+        .bind(("language", query.language.map(|l| l.to_lowercase())))
+        .bind(("cursor_value", cursor_value))
+        .bind(("cursor_name", cursor_name))
+        .bind(("limit", limit + 1))
+        .await?
+        .take(0)
+    }
+    .await;
+    let Ok(mut games) = games else {
         return (
-            StatusCode::NOT_FOUND,
+            StatusCode::INTERNAL_SERVER_ERROR,
             Json(BaseResponse {
                 success: false,
-                message: Some("No game found".to_string()),
+                message: Some("Failed to get games".to_string()),
             })
             .into_response(),
         );
+    };
+    let next_cursor = if games.len() > limit {
+        games.truncate(limit);
+        games.last().map(|g| {
+            let value = match field {
+                "created_at" => g.created_at,
+                "updated_at" => g.updated_at,
+                _ => 0,
+            };
+            encode_cursor(value, &g.name)
+        })
+    } else {
+        None
+    };
+    let mut response = Json(games).into_response();
+    if let Some(next_cursor) = next_cursor.and_then(|c| HeaderValue::from_str(&c).ok()) {
+        response.headers_mut().insert("x-next-cursor", next_cursor);
     }
+    (StatusCode::OK, response)
+}
 
-    let game = game_optional.unwrap().unwrap();
-    // Create a random short id for the game here
-    let id = id_generator(6);
-    let lobby = Lobby::new(game.text_section.clone());
-
-    let mut state = state.write().unwrap();
-    state.insert(id.clone(), RwLock::new(lobby));
-
-    // TODO generate qr code to join the game
+// Cursors of the game listing are the sort value and name of the last game of a page, base64
+// encoded so they can be sent in a header
+fn encode_cursor(value: u64, name: &str) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{}:{}", value, name))
+}
 
-    (
-        StatusCode::OK,
-        Json(BaseStringDTO { name: id }).into_response(),
-    )
+fn decode_cursor(cursor: &str) -> Option<(u64, String)> {
+    let decoded = String::from_utf8(URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()?;
+    let (value, name) = decoded.split_once(':')?;
+    Some((value.parse().ok()?, name.to_string()))
 }
 
-// Url: /api/admin/active
-// Returns the active games
+// Url: /api/admin/game/{name}
+// Returns the latest version of the game with the specified name
 // Method: GET
-// Response: Vec<BaseStringDTO{id: String}>
+// Response: GameVersionDTO
 #[utoipa::path(
     get,
-    path = "/api/admin/active",
+    path = "/api/admin/game/{name}",
+    params(
+        ("name" = String, Path, description = "Name of the game")
+    ),
     security(
-        ("ApiKey" = [])
+        ("ApiKey" = []),
+        ("Bearer" = [])
     ),
     responses(
-        (status = 200, description = "Active games retrieved successfully", body = [BaseStringDTO]),
-        (status = 500, description = "Failed to get active games", body = BaseResponse)
+        (status = 200, description = "Game retrieved successfully", body = GameVersionDTO),
+        (status = 404, description = "No game found", body = BaseResponse),
+        (status = 500, description = "Failed to get game", body = BaseResponse)
     )
 )]
-pub async fn active_games_handler(State(state): State<SharedAppState>) -> impl IntoResponse {
-    // Get the active games from the temporary game state here
-    let exclusive_state = state.read().unwrap();
-    let active_games = exclusive_state
-        .keys()
-        .map(|id| BaseStringDTO { name: id.clone() })
-        .collect::<Vec<BaseStringDTO>>();
-    (StatusCode::OK, Json(active_games).into_response())
+pub async fn get_game_handler(Path(name): Path<String>) -> impl IntoResponse {
+    let con: &Surreal<Client> = DB.get().await;
+    let game: surrealdb::Result<Option<GameVersionDTO>> = con.select(("game", name)).await;
+    match game {
+        Ok(Some(game)) => (StatusCode::OK, Json(game).into_response()),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(BaseResponse {
+                success: false,
+                message: Some("No game found".to_string()),
+            })
+            .into_response(),
+        ),
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Failed to get game".to_string()),
+            })
+            .into_response(),
+        ),
+    }
 }
 
-// Url: /api/admin/close
-// Closes a game with the specified id
-// Method: POST
-// Request: BaseStringDTO{id: String}
+// Url: /api/admin/game/{name}
+// Partially updates the game with the specified name and saves it as a new version
+// Method: PATCH
+// Request: GameUpdateDTO{text_section: Option<Vec<String>>}
 // Response: BaseResponse
 #[utoipa::path(
-    post,
-    path = "/api/admin/close",
+    patch,
+    path = "/api/admin/game/{name}",
+    params(
+        ("name" = String, Path, description = "Name of the game")
+    ),
     security(
-        ("ApiKey" = [])
+        ("ApiKey" = []),
+        ("Bearer" = [])
     ),
-    request_body = BaseStringDTO,
+    request_body = GameUpdateDTO,
     responses(
-        (status = 200, description = "Game closed", body = BaseResponse),
-        (status = 404, description = "Game not found", body = BaseResponse)
+        (status = 200, description = "Game updated", body = BaseResponse),
+        (status = 404, description = "No game found", body = BaseResponse),
+        (status = 422, description = "Invalid game template", body = ValidationResponse),
+        (status = 500, description = "Failed to update game", body = BaseResponse)
     )
 )]
-pub async fn close_game_handler(
-    state: State<SharedAppState>,
-    payload: Json<BaseStringDTO>,
+pub async fn update_game_handler(
+    Path(name): Path<String>,
+    Json(payload): Json<GameUpdateDTO>,
 ) -> impl IntoResponse {
-    // Close the game with the specified id here
-    let mut exclusive_state = state.write().unwrap();
-    let option = exclusive_state.remove(&payload.name);
-    if option.is_none() {
+    let con: &Surreal<Client> = DB.get().await;
+    let existing: surrealdb::Result<Option<GameVersionDTO>> =
+        con.select(("game", name.clone())).await;
+    let existing = match existing {
+        Ok(Some(existing)) => existing,
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("No game found".to_string()),
+                })
+                .into_response(),
+            );
+        }
+        Err(_) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("Failed to update game".to_string()),
+                })
+                .into_response(),
+            );
+        }
+    };
+    let metadata = existing.metadata.clone();
+    let text_changed = payload.text_section.is_some() || payload.text.is_some();
+    let updated = GameDTO {
+        name,
+        text: payload.text,
+        text_section: payload
+            .text_section
+            .unwrap_or(existing.text_section.clone()),
+        // hints of the old text are dropped if only the text sections change
+        hints: payload.hints.unwrap_or(if text_changed {
+            vec![]
+        } else {
+            existing.hints.clone()
+        }),
+        constraints: payload.constraints.unwrap_or(if text_changed {
+            vec![]
+        } else {
+            existing.constraints.clone()
+        }),
+        metadata: GameMetadataDTO {
+            description: payload.description.unwrap_or(metadata.description),
+            tags: payload.tags.unwrap_or(metadata.tags),
+            language: payload.language.unwrap_or(metadata.language),
+            author: payload.author.unwrap_or(metadata.author),
+            age_rating: payload.age_rating.unwrap_or(metadata.age_rating),
+        },
+    };
+    let updated = match expand_placeholders(updated) {
+        Ok(updated) => updated,
+        Err(errors) => {
+            return (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(ValidationResponse {
+                    success: false,
+                    message: Some("Invalid game template".to_string()),
+                    errors,
+                })
+                .into_response(),
+            );
+        }
+    };
+    let errors = validate_game(&updated);
+    if !errors.is_empty() {
         return (
-            StatusCode::NOT_FOUND,
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ValidationResponse {
+                success: false,
+                message: Some("Invalid game template".to_string()),
+                errors,
+            })
+            .into_response(),
+        );
+    }
+    let saved = save_game_version(con, updated, Some(existing)).await;
+    if saved.is_err() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
             Json(BaseResponse {
                 success: false,
-                message: Some("Game not found".to_string()),
+                message: Some("Failed to update game".to_string()),
             })
             .into_response(),
         );
@@ -309,35 +698,1199 @@ pub async fn close_game_handler(
         StatusCode::OK,
         Json(BaseResponse {
             success: true,
-            message: None,
+            message: Some(format!("Game saved as version {}", saved.unwrap().version)),
         })
         .into_response(),
     )
 }
 
-// Url /api/admin/startfill
-// Starts the filling process for the specified gap
+// Url: /api/admin/game/{name}/rename
+// Renames the game with the specified name including its version history
 // Method: POST
-// Request: BaseStringDTO{id: String}
+// Request: BaseStringDTO{name: String}
 // Response: BaseResponse
 #[utoipa::path(
     post,
-    path = "/api/admin/startfill",
+    path = "/api/admin/game/{name}/rename",
+    params(
+        ("name" = String, Path, description = "Current name of the game")
+    ),
     security(
-        ("ApiKey" = [])
+        ("ApiKey" = []),
+        ("Bearer" = [])
     ),
     request_body = BaseStringDTO,
     responses(
-        (status = 200, description = "Filling started", body = BaseResponse),
-        (status = 404, description = "Game not found", body = BaseResponse)
+        (status = 200, description = "Game renamed", body = BaseResponse),
+        (status = 404, description = "No game found", body = BaseResponse),
+        (status = 409, description = "A game with the new name already exists", body = BaseResponse),
+        (status = 422, description = "Invalid game name", body = ValidationResponse),
+        (status = 500, description = "Failed to rename game", body = BaseResponse)
     )
 )]
-pub async fn start_fill_handler(
-    state: State<SharedAppState>,
-    payload: Json<BaseStringDTO>,
+pub async fn rename_game_handler(
+    Path(name): Path<String>,
+    Json(payload): Json<BaseStringDTO>,
 ) -> impl IntoResponse {
-    // Start the filling process for the specified gap here
-    let exclusive_state = state.read().unwrap();
+    let con: &Surreal<Client> = DB.get().await;
+    let existing: surrealdb::Result<Option<GameVersionDTO>> =
+        con.select(("game", name.clone())).await;
+    let taken: surrealdb::Result<Option<GameVersionDTO>> =
+        con.select(("game", payload.name.clone())).await;
+    let existing = match (existing, taken) {
+        (Ok(Some(existing)), Ok(None)) => existing,
+        (Ok(None), Ok(_)) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("No game found".to_string()),
+                })
+                .into_response(),
+            );
+        }
+        (Ok(Some(_)), Ok(Some(_))) => {
+            return (
+                StatusCode::CONFLICT,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("A game with the new name already exists".to_string()),
+                })
+                .into_response(),
+            );
+        }
+        _ => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("Failed to rename game".to_string()),
+                })
+                .into_response(),
+            );
+        }
+    };
+    let renamed = GameVersionDTO {
+        name: payload.name.clone(),
+        ..existing
+    };
+    let errors = validate_game(&GameDTO {
+        name: renamed.name.clone(),
+        text: None,
+        text_section: renamed.text_section.clone(),
+        hints: renamed.hints.clone(),
+        constraints: renamed.constraints.clone(),
+        metadata: renamed.metadata.clone(),
+    });
+    if !errors.is_empty() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ValidationResponse {
+                success: false,
+                message: Some("Invalid game name".to_string()),
+                errors,
+            })
+            .into_response(),
+        );
+    }
+    // the template and its history are moved together or not at all
+    let moved: surrealdb::Result<_> = async {
+        con.query(
+            "BEGIN TRANSACTION;
+            CREATE type::thing('game', $new_name) CONTENT $game;
+            UPDATE game_version SET name = $new_name WHERE name = $name;
+            DELETE type::thing('game', $name);
+            COMMIT TRANSACTION;",
+        )
+        .bind(("game", renamed))
+        .bind(("new_name", payload.name.clone()))
+        .bind(("name", name))
+        .await?
+        .check()
+    }
+    .await;
+    if moved.is_err() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Failed to rename game".to_string()),
+            })
+            .into_response(),
+        );
+    }
+    (
+        StatusCode::OK,
+        Json(BaseResponse {
+            success: true,
+            message: Some(format!("Game renamed to {}", payload.name)),
+        })
+        .into_response(),
+    )
+}
+
+// Url: /api/admin/game/{name}
+// Deletes the game with the specified name including its version history
+// Method: DELETE
+// Response: BaseResponse
+#[utoipa::path(
+    delete,
+    path = "/api/admin/game/{name}",
+    params(
+        ("name" = String, Path, description = "Name of the game")
+    ),
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    responses(
+        (status = 200, description = "Game deleted", body = BaseResponse),
+        (status = 404, description = "No game found", body = BaseResponse),
+        (status = 500, description = "Failed to delete game", body = BaseResponse)
+    )
+)]
+pub async fn delete_game_handler(Path(name): Path<String>) -> impl IntoResponse {
+    let con: &Surreal<Client> = DB.get().await;
+    let existing: surrealdb::Result<Option<GameVersionDTO>> =
+        con.select(("game", name.clone())).await;
+    match existing {
+        Ok(Some(_)) => (),
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("No game found".to_string()),
+                })
+                .into_response(),
+            );
+        }
+        Err(_) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("Failed to delete game".to_string()),
+                })
+                .into_response(),
+            );
+        }
+    }
+    // the template and its history are deleted together or not at all
+    let deleted: surrealdb::Result<_> = async {
+        con.query(
+            "BEGIN TRANSACTION;
+            DELETE type::thing('game', $name);
+            DELETE game_version WHERE name = $name;
+            COMMIT TRANSACTION;",
+        )
+        .bind(("name", name))
+        .await?
+        .check()
+    }
+    .await;
+    if deleted.is_err() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Failed to delete game".to_string()),
+            })
+            .into_response(),
+        );
+    }
+    (
+        StatusCode::OK,
+        Json(BaseResponse {
+            success: true,
+            message: None,
+        })
+        .into_response(),
+    )
+}
+
+// Url: /api/admin/versions
+// Returns all versions of the game with the specified name
+// Method: GET
+// Response: Vec<GameVersionDTO>
+#[utoipa::path(
+    get,
+    path = "/api/admin/versions",
+    params(
+        ("name" = String, Query, description = "Name of the game")
+    ),
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    responses(
+        (status = 200, description = "Versions retrieved successfully", body = [GameVersionDTO]),
+        (status = 404, description = "No game found", body = BaseResponse),
+        (status = 500, description = "Failed to get versions", body = BaseResponse)
+    )
+)]
+pub async fn game_versions_handler(Query(query): Query<TemplateQuery>) -> impl IntoResponse {
+    let con: &Surreal<Client> = DB.get().await;
+    let versions = load_game_versions(con, query.name).await;
+    if versions.is_err() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Failed to get versions".to_string()),
+            })
+            .into_response(),
+        );
+    }
+    let versions = versions.unwrap();
+    if versions.is_empty() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(BaseResponse {
+                success: false,
+                message: Some("No game found".to_string()),
+            })
+            .into_response(),
+        );
+    }
+    (StatusCode::OK, Json(versions).into_response())
+}
+
+// Url: /api/admin/diff
+// Compares two versions of the game with the specified name
+// Method: GET
+// Response: GameDiffDTO
+#[utoipa::path(
+    get,
+    path = "/api/admin/diff",
+    params(
+        ("name" = String, Query, description = "Name of the game"),
+        ("from" = u32, Query, description = "Version to compare from"),
+        ("to" = u32, Query, description = "Version to compare to")
+    ),
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    responses(
+        (status = 200, description = "Changed text sections between both versions", body = GameDiffDTO),
+        (status = 404, description = "Version not found", body = BaseResponse)
+    )
+)]
+pub async fn game_diff_handler(Query(query): Query<DiffQuery>) -> impl IntoResponse {
+    let con: &Surreal<Client> = DB.get().await;
+    let from = load_game_version(con, query.name.clone(), query.from).await;
+    let to = load_game_version(con, query.name.clone(), query.to).await;
+    match (from, to) {
+        (Ok(Some(from)), Ok(Some(to))) => (
+            StatusCode::OK,
+            Json(GameDiffDTO {
+                name: query.name,
+                from: from.version,
+                to: to.version,
+                sections: diff_sections(&from.text_section, &to.text_section),
+            })
+            .into_response(),
+        ),
+        _ => (
+            StatusCode::NOT_FOUND,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Version not found".to_string()),
+            })
+            .into_response(),
+        ),
+    }
+}
+
+// Url: /api/admin/restore
+// Restores an older version of a game by saving its text as new latest version
+// Method: POST
+// Request: RestoreDTO{name: String, version: u32}
+// Response: BaseResponse
+#[utoipa::path(
+    post,
+    path = "/api/admin/restore",
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    request_body = RestoreDTO,
+    responses(
+        (status = 200, description = "Version restored", body = BaseResponse),
+        (status = 404, description = "Version not found", body = BaseResponse),
+        (status = 500, description = "Failed to restore version", body = BaseResponse)
+    )
+)]
+pub async fn restore_game_handler(Json(payload): Json<RestoreDTO>) -> impl IntoResponse {
+    let con: &Surreal<Client> = DB.get().await;
+    let old = load_game_version(con, payload.name.clone(), payload.version).await;
+    let latest: surrealdb::Result<Option<GameVersionDTO>> =
+        con.select(("game", payload.name.clone())).await;
+    let (old, latest) = match (old, latest) {
+        (Ok(Some(old)), Ok(latest)) => (old, latest),
+        _ => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("Version not found".to_string()),
+                })
+                .into_response(),
+            );
+        }
+    };
+    let restored = GameDTO {
+        name: payload.name,
+        text: None,
+        text_section: old.text_section,
+        hints: old.hints,
+        constraints: old.constraints,
+        metadata: old.metadata,
+    };
+    let saved = save_game_version(con, restored, latest).await;
+    if saved.is_err() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Failed to restore version".to_string()),
+            })
+            .into_response(),
+        );
+    }
+    (
+        StatusCode::OK,
+        Json(BaseResponse {
+            success: true,
+            message: Some(format!(
+                "Restored version {} as version {}",
+                payload.version,
+                saved.unwrap().version
+            )),
+        })
+        .into_response(),
+    )
+}
+
+// Url: /api/admin/import
+// Imports a file of game templates in the specified format
+// Method: POST
+// Request: file content in jsonl, yaml or csv format
+// Response: ImportReportDTO
+#[utoipa::path(
+    post,
+    path = "/api/admin/import",
+    params(
+        ("format" = String, Query, description = "File format: jsonl, yaml or csv"),
+        ("dry_run" = Option<bool>, Query, description = "Only report what would happen"),
+        ("update" = Option<bool>, Query, description = "Save existing games as new version instead of skipping them")
+    ),
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    request_body(content = String, description = "Templates in jsonl, yaml or csv format", content_type = "text/plain"),
+    responses(
+        (status = 200, description = "Report of the imported templates per row", body = ImportReportDTO),
+        (status = 400, description = "Unknown format or unreadable file", body = BaseResponse)
+    )
+)]
+pub async fn import_games_handler(
+    Query(query): Query<ImportQuery>,
+    body: String,
+) -> impl IntoResponse {
+    let rows = match parse_games(&query.format, &body) {
+        Ok(rows) => rows,
+        Err(message) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some(message),
+                })
+                .into_response(),
+            );
+        }
+    };
+    let dry_run = query.dry_run.unwrap_or(false);
+    let update = query.update.unwrap_or(false);
+    let con: &Surreal<Client> = DB.get().await;
+
+    let mut seen: Vec<String> = vec![];
+    let mut report: Vec<ImportRowDTO> = vec![];
+    for (row, parsed) in rows {
+        let game = match parsed {
+            Ok(game) => expand_placeholders(game),
+            Err(message) => Err(vec![ValidationErrorDTO {
+                field: "row".to_string(),
+                message,
+            }]),
+        };
+        let game = game.and_then(|game| {
+            let mut errors = validate_game(&game);
+            if seen.contains(&game.name) {
+                errors.push(ValidationErrorDTO {
+                    field: "name".to_string(),
+                    message: "Name is used more than once in the file".to_string(),
+                });
+            }
+            if errors.is_empty() {
+                Ok(game)
+            } else {
+                Err(errors)
+            }
+        });
+        let game = match game {
+            Ok(game) => game,
+            Err(errors) => {
+                report.push(ImportRowDTO {
+                    row,
+                    name: None,
+                    status: "error".to_string(),
+                    errors,
+                });
+                continue;
+            }
+        };
+        seen.push(game.name.clone());
+        let name = game.name.clone();
+
+        let exists: surrealdb::Result<Option<GameVersionDTO>> =
+            con.select(("game", name.clone())).await;
+        let (status, previous) = match exists {
+            Ok(None) => ("created", None),
+            Ok(Some(previous)) if update => ("updated", Some(previous)),
+            Ok(Some(_)) => ("skipped", None),
+            Err(_) => ("error", None),
+        };
+        let mut errors = vec![];
+        if status == "error" {
+            errors.push(ValidationErrorDTO {
+                field: "name".to_string(),
+                message: "Failed to check if game exists".to_string(),
+            });
+        } else if !dry_run
+            && status != "skipped"
+            && save_game_version(con, game, previous).await.is_err()
+        {
+            errors.push(ValidationErrorDTO {
+                field: "name".to_string(),
+                message: "Failed to insert game".to_string(),
+            });
+        }
+        report.push(ImportRowDTO {
+            row,
+            name: Some(name),
+            status: if errors.is_empty() { status } else { "error" }.to_string(),
+            errors,
+        });
+    }
+
+    let count = |status: &str| report.iter().filter(|r| r.status == status).count();
+    (
+        StatusCode::OK,
+        Json(ImportReportDTO {
+            success: count("error") == 0,
+            dry_run,
+            created: count("created"),
+            updated: count("updated"),
+            skipped: count("skipped"),
+            failed: count("error"),
+            rows: report,
+        })
+        .into_response(),
+    )
+}
+
+// Url: /api/admin/export
+// Exports the latest version of all game templates in the specified format
+// Method: GET
+// Response: file content in jsonl, yaml or csv format
+#[utoipa::path(
+    get,
+    path = "/api/admin/export",
+    params(
+        ("format" = String, Query, description = "File format: jsonl, yaml or csv")
+    ),
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    responses(
+        (status = 200, description = "All templates in the requested format", body = String),
+        (status = 400, description = "Unknown format", body = BaseResponse),
+        (status = 500, description = "Failed to export games", body = BaseResponse)
+    )
+)]
+pub async fn export_games_handler(Query(query): Query<ExportQuery>) -> impl IntoResponse {
+    let con: &Surreal<Client> = DB.get().await;
+    let games: surrealdb::Result<Vec<GameVersionDTO>> = con.select("game").await;
+    if games.is_err() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Failed to export games".to_string()),
+            })
+            .into_response(),
+        );
+    }
+    let mut games = games.unwrap();
+    games.sort_by(|a, b| a.name.cmp(&b.name));
+    match export_games(&query.format, games) {
+        Ok(file) => (
+            StatusCode::OK,
+            (
+                [
+                    (
+                        header::CONTENT_TYPE,
+                        content_type(&query.format).to_string(),
+                    ),
+                    (
+                        header::CONTENT_DISPOSITION,
+                        format!("attachment; filename=\"games.{}\"", query.format),
+                    ),
+                ],
+                file,
+            )
+                .into_response(),
+        ),
+        Err(message) => (
+            StatusCode::BAD_REQUEST,
+            Json(BaseResponse {
+                success: false,
+                message: Some(message),
+            })
+            .into_response(),
+        ),
+    }
+}
+
+// Url: /api/admin/generate
+// Generates a draft game from plain text by blanking words and optionally saves it
+// Method: POST
+// Request: GenerateGameDTO
+// Response: GeneratedGameDTO
+#[utoipa::path(
+    post,
+    path = "/api/admin/generate",
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    request_body = GenerateGameDTO,
+    responses(
+        (status = 200, description = "Draft generated (and saved if requested)", body = GeneratedGameDTO),
+        (status = 400, description = "Unknown mode, empty marker, missing words or missing name to save", body = BaseResponse),
+        (status = 409, description = "Game already exists", body = BaseResponse),
+        (status = 422, description = "Draft can't be saved, it is not a valid game", body = ValidationResponse),
+        (status = 500, description = "Failed to insert game", body = BaseResponse)
+    ),
+    description = "Blanks words of the text at explicit markers (`markers`), at the given words \
+    (`words`) or every Nth word that is not a stop word (`every_nth`) and returns the draft for \
+    review. With `save` the draft is stored as a new game."
+)]
+pub async fn generate_game_handler(Json(payload): Json<GenerateGameDTO>) -> impl IntoResponse {
+    let words = payload.words.unwrap_or_default();
+    let mode = match payload.mode.as_str() {
+        "markers" if payload.marker.as_ref().is_some_and(|m| m.trim().is_empty()) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("Marker must not be empty".to_string()),
+                })
+                .into_response(),
+            );
+        }
+        "markers" => BlankMode::Markers(payload.marker.as_deref().unwrap_or(DEFAULT_MARKER)),
+        "words" if !words.is_empty() => BlankMode::Words(&words),
+        "every_nth" => BlankMode::EveryNth(payload.every.unwrap_or(DEFAULT_EVERY)),
+        "words" => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("Words to blank are required".to_string()),
+                })
+                .into_response(),
+            );
+        }
+        _ => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("Mode must be one of markers, words or every_nth".to_string()),
+                })
+                .into_response(),
+            );
+        }
+    };
+    let save = payload.save.unwrap_or(false);
+    if save && payload.name.is_none() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(BaseResponse {
+                success: false,
+                message: Some("A name is required to save the draft".to_string()),
+            })
+            .into_response(),
+        );
+    }
+
+    let (text_section, blanked) = generate_sections(&payload.text, &mode);
+    let game = GameDTO {
+        name: payload.name.unwrap_or("draft".to_string()),
+        text: None,
+        text_section,
+        hints: vec![],
+        constraints: vec![],
+        metadata: GameMetadataDTO::default(),
+    };
+    let errors = validate_game(&game);
+    if !save {
+        return (
+            StatusCode::OK,
+            Json(GeneratedGameDTO {
+                success: errors.is_empty(),
+                saved: false,
+                game,
+                blanked,
+                errors,
+            })
+            .into_response(),
+        );
+    }
+    if !errors.is_empty() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ValidationResponse {
+                success: false,
+                message: Some("Invalid game template".to_string()),
+                errors,
+            })
+            .into_response(),
+        );
+    }
+
+    let con: &Surreal<Client> = DB.get().await;
+    let exists: surrealdb::Result<Option<GameVersionDTO>> =
+        con.select(("game", game.name.clone())).await;
+    match exists {
+        Ok(None) => (),
+        Ok(Some(_)) => {
+            return (
+                StatusCode::CONFLICT,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("Game already exists".to_string()),
+                })
+                .into_response(),
+            );
+        }
+        Err(_) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("Failed to check if game exists".to_string()),
+                })
+                .into_response(),
+            );
+        }
+    }
+    if save_game_version(con, game.clone(), None).await.is_err() {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Failed to insert game".to_string()),
+            })
+            .into_response(),
+        );
+    }
+    (
+        StatusCode::OK,
+        Json(GeneratedGameDTO {
+            success: true,
+            saved: true,
+            game,
+            blanked,
+            errors,
+        })
+        .into_response(),
+    )
+}
+
+// Picks a random game template matching all of the specified criteria
+async fn pick_random_game(
+    con: &Surreal<Client>,
+    criteria: &RandomGameDTO,
+) -> surrealdb::Result<Option<GameVersionDTO>> {
+    let recent: Vec<String> = match criteria.not_played_in_last {
+        Some(sessions) if sessions > 0 => {
+            let mut response = con
+                .query("SELECT * FROM session ORDER BY started_at DESC LIMIT $sessions")
+                .bind(("sessions", sessions))
+                .await?;
+            let sessions: Vec<SessionDTO> = response.take(0)?;
+            sessions.into_iter().map(|s| s.template).collect()
+        }
+        _ => vec![],
+    };
+    // the candidates are filtered and one of them is picked in the db
+    let mut conditions = vec!["name NOTINSIDE $recent"];
+    if criteria.tag.is_some() {
+        conditions.push("$tag IN tags");
+    }
+    if criteria.language.is_some() {
+        conditions.push("string::lowercase(language ?? '') = $language");
+    }
+    if criteria.min_gaps.is_some() {
+        conditions.push("array::len(text_section) - 1 >= $min_gaps");
+    }
+    if criteria.max_gaps.is_some() {
+        conditions.push("array::len(text_section) - 1 <= $max_gaps");
+    }
+    let mut response = con
+        .query(format!(
+            "SELECT * FROM game WHERE {} ORDER BY rand() LIMIT 1",
+            conditions.join(" AND ")
+        ))
+        .bind(("recent", recent))
+        .bind((
+            "tag",
+            criteria.tag.as_ref().map(|t| t.trim().to_lowercase()),
+        ))
+        .bind((
+            "language",
+            criteria.language.as_ref().map(|l| l.to_lowercase()),
+        ))
+        .bind(("min_gaps", criteria.min_gaps))
+        .bind(("max_gaps", criteria.max_gaps))
+        .await?;
+    response.take(0)
+}
+
+// Url: /api/admin/start
+// Starts a game with the specified id (or a random one matching the criteria) and loads it from db
+// to a temporary game state in memory and create a random short id for the game
+// Method: POST
+// Request: StartGameDTO{name: Option<String>, random: Option<RandomGameDTO>, version: Option<u32>, teams: Option<Vec<String>>, settings: Option<LobbySettings>}
+// Response: StartGameResponse{name: String, template: String, version: u32}
+#[utoipa::path(
+    post,
+    path = "/api/admin/start",
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    request_body = StartGameDTO,
+    responses(
+        (status = 200, description = "Game started", body = StartGameResponse),
+        (status = 400, description = "Invalid teams, neither or both of name and random criteria, or a version with random criteria", body = BaseResponse),
+        (status = 404, description = "No game found", body = BaseResponse),
+        (status = 422, description = "Invalid settings", body = ValidationResponse)
+    ),
+    description = "Starts a lobby for the latest or the pinned version of the game with the \
+    specified name, or for a random game matching the `random` criteria. The response contains \
+    the lobby id as `name` and the chosen template. If teams are provided, the lobby is played \
+    in teams mode. Settings that are not provided fall back to their defaults."
+)]
+pub async fn start_game_handler(
+    State(state): State<SharedAppState>,
+    Json(payload): Json<StartGameDTO>,
+) -> impl IntoResponse {
+    if let Some(teams) = &payload.teams {
+        let mut unique = teams.clone();
+        unique.sort();
+        unique.dedup();
+        if teams.len() < 2
+            || unique.len() != teams.len()
+            || teams.iter().any(|t| t.trim().is_empty())
+        {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some(
+                        "At least two unique, non-empty team names are required".to_string(),
+                    ),
+                })
+                .into_response(),
+            );
+        }
+    }
+
+    let settings = payload.settings.clone().unwrap_or_default();
+    let errors = validate_settings(&settings);
+    if !errors.is_empty() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ValidationResponse {
+                success: false,
+                message: Some("Invalid settings".to_string()),
+                errors,
+            })
+            .into_response(),
+        );
+    }
+
+    // Load the game from the SurrealDB and create a temporary game state here
+    let con: &Surreal<Client> = DB.get().await;
+    if settings.moderation != ModerationMode::Off {
+        if let Err(err) = load_word_lists(con).await {
+            event!(Level::ERROR, "Failed to load word lists: {}", err);
+        }
+    }
+    let bad_request = |message: &str| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(BaseResponse {
+                success: false,
+                message: Some(message.to_string()),
+            })
+            .into_response(),
+        )
+    };
+    let game_optional: surrealdb::Result<Option<GameVersionDTO>> =
+        match (&payload.name, &payload.random, payload.version) {
+            (Some(name), None, Some(version)) => {
+                load_game_version(con, name.clone(), version).await
+            }
+            (Some(name), None, None) => con.select(("game", name.clone())).await,
+            (None, Some(criteria), None) => pick_random_game(con, criteria).await,
+            (None, None, _) => return bad_request("Either a name or random criteria are required"),
+            (Some(_), Some(_), _) => {
+                return bad_request("A name and random criteria can't be combined")
+            }
+            (None, Some(_), Some(_)) => {
+                return bad_request("A version can only be pinned together with a name")
+            }
+        };
+    if game_optional.is_err() || game_optional.as_ref().unwrap().is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(BaseResponse {
+                success: false,
+                message: Some("No game found".to_string()),
+            })
+            .into_response(),
+        );
+    }
+
+    let game = game_optional.unwrap().unwrap();
+    // Create a random short id for the game here
+    let id = id_generator(6);
+    let response = StartGameResponse {
+        name: id.clone(),
+        template: game.name.clone(),
+        version: game.version,
+    };
+    let lobby = Lobby::new(game, payload.teams.clone(), settings);
+
+    {
+        let mut state = state.write().unwrap();
+        state.insert(id.clone(), RwLock::new(lobby));
+    }
+
+    // remember the session, so random picks can skip recently played templates
+    let session: surrealdb::Result<Option<SessionDTO>> = con
+        .create("session")
+        .content(SessionDTO {
+            lobby: id,
+            template: response.template.clone(),
+            version: response.version,
+            started_at: unix_timestamp(),
+        })
+        .await;
+    if let Err(err) = session {
+        event!(Level::ERROR, "Failed to store session: {}", err);
+    }
+
+    // TODO generate qr code to join the game
+
+    (StatusCode::OK, Json(response).into_response())
+}
+
+// Url: /api/admin/active
+// Returns the active games
+// Method: GET
+// Response: Vec<ActiveGameDTO{name: String, template: String, version: u32}>
+#[utoipa::path(
+    get,
+    path = "/api/admin/active",
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    responses(
+        (status = 200, description = "Active games retrieved successfully", body = [ActiveGameDTO]),
+        (status = 500, description = "Failed to get active games", body = BaseResponse)
+    )
+)]
+pub async fn active_games_handler(State(state): State<SharedAppState>) -> impl IntoResponse {
+    // Get the active games from the temporary game state here
+    let exclusive_state = state.read().unwrap();
+    let active_games = exclusive_state
+        .iter()
+        .map(|(id, lobby)| {
+            let lobby = lobby.read().unwrap();
+            ActiveGameDTO {
+                name: id.clone(),
+                template: lobby.template.clone(),
+                version: lobby.version,
+            }
+        })
+        .collect::<Vec<ActiveGameDTO>>();
+    (StatusCode::OK, Json(active_games).into_response())
+}
+
+// Url: /api/admin/close
+// Closes a game with the specified id
+// Method: POST
+// Request: BaseStringDTO{id: String}
+// Response: BaseResponse
+#[utoipa::path(
+    post,
+    path = "/api/admin/close",
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    request_body = BaseStringDTO,
+    responses(
+        (status = 200, description = "Game closed", body = BaseResponse),
+        (status = 404, description = "Game not found", body = BaseResponse)
+    )
+)]
+pub async fn close_game_handler(
+    state: State<SharedAppState>,
+    payload: Json<BaseStringDTO>,
+) -> impl IntoResponse {
+    // Close the game with the specified id here
+    let mut exclusive_state = state.write().unwrap();
+    let option = exclusive_state.remove(&payload.name);
+    if option.is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Game not found".to_string()),
+            })
+            .into_response(),
+        );
+    }
+    (
+        StatusCode::OK,
+        Json(BaseResponse {
+            success: true,
+            message: None,
+        })
+        .into_response(),
+    )
+}
+
+// Url: /api/admin/kick
+// Removes a user from a game
+// Method: POST
+// Request: KickDTO{name: String, id: String}
+// Response: BaseResponse
+#[utoipa::path(
+    post,
+    path = "/api/admin/kick",
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    request_body = KickDTO,
+    responses(
+        (status = 200, description = "User removed", body = BaseResponse),
+        (status = 404, description = "Game not found | User not found", body = BaseResponse)
+    ),
+    description = "Removes a user from the game and releases the gaps the user claimed but did not \
+    fill. The user gets a `kicked` message and is disconnected, the other users get a `user_left` \
+    message."
+)]
+pub async fn kick_user_handler(
+    State(state): State<SharedAppState>,
+    Json(payload): Json<KickDTO>,
+) -> impl IntoResponse {
+    let read_state = state.read().unwrap();
+    let Some(lobby) = read_state.get(&payload.name) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Game not found".to_string()),
+            })
+            .into_response(),
+        );
+    };
+    let token = lobby.read().unwrap().token_of(&payload.id);
+    let Some(token) = token.filter(|token| remove_user(lobby, token)) else {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(BaseResponse {
+                success: false,
+                message: Some("User not found".to_string()),
+            })
+            .into_response(),
+        );
+    };
+    let lobby = lobby.read().unwrap();
+    let connections = &lobby.game.connections;
+    connections.send_to(
+        &token,
+        &ServerMessage::Kicked("Removed by the host".to_string()),
+    );
+    connections.disconnect_user(&token);
+    (
+        StatusCode::OK,
+        Json(BaseResponse {
+            success: true,
+            message: None,
+        })
+        .into_response(),
+    )
+}
+
+// Url: /api/admin/metrics
+// Returns counters of the websocket connections, e.g. how often clients lagged behind
+// Method: GET
+// Response: WebsocketMetricsDTO
+#[utoipa::path(
+    get,
+    path = "/api/admin/metrics",
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    responses(
+        (status = 200, description = "Websocket metrics retrieved successfully", body = WebsocketMetricsDTO)
+    ),
+    description = "Counts since the server was started. Clients that lag behind the lobby events \
+    by more than `channel_capacity` events skip them and get a `snapshot` message instead."
+)]
+pub async fn websocket_metrics_handler() -> impl IntoResponse {
+    (StatusCode::OK, Json(websocket::metrics()).into_response())
+}
+
+// Url /api/admin/startfill
+// Starts the filling process for the specified gap
+// Method: POST
+// Request: BaseStringDTO{id: String}
+// Response: BaseResponse
+#[utoipa::path(
+    post,
+    path = "/api/admin/startfill",
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    request_body = BaseStringDTO,
+    responses(
+        (status = 200, description = "Filling started", body = BaseResponse),
+        (status = 400, description = "Game is not waiting for players anymore", body = BaseResponse),
+        (status = 404, description = "Game not found", body = BaseResponse)
+    )
+)]
+pub async fn start_fill_handler(
+    state: State<SharedAppState>,
+    payload: Json<BaseStringDTO>,
+) -> impl IntoResponse {
+    // Start the filling process for the specified gap here
+    let exclusive_state = state.read().unwrap();
+    let lobby = exclusive_state.get(&payload.name);
+    if lobby.is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Game not found".to_string()),
+            })
+            .into_response(),
+        );
+    }
+    let mut lobby = lobby.unwrap().write().unwrap();
+    if lobby.game.view != "waiting" {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Game is not waiting for players anymore".to_string()),
+            })
+            .into_response(),
+        );
+    }
+    lobby.game.view = "fill".to_string();
+    // notify all users that the filling process has started
+    lobby
+        .game
+        .connections
+        .send_all(ServerMessage::ChangeView("fill".to_string()));
+    let timer = lobby
+        .settings
+        .fill_time_limit
+        .map(|limit| schedule_fill_time_limit(&state, &payload.name, limit));
+    lobby.game.set_timer(timer);
+    (
+        StatusCode::OK,
+        Json(BaseResponse {
+            success: true,
+            message: None,
+        })
+        .into_response(),
+    )
+}
+
+// Url: /api/admin/settings
+// Changes the settings of a lobby while it is waiting for players
+// Method: POST
+// Request: LobbySettingsDTO{name: String, settings: LobbySettings}
+// Response: BaseResponse
+#[utoipa::path(
+    post,
+    path = "/api/admin/settings",
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    request_body = LobbySettingsDTO,
+    responses(
+        (status = 200, description = "Settings changed", body = BaseResponse),
+        (status = 400, description = "Game is not waiting for players anymore", body = BaseResponse),
+        (status = 404, description = "Game not found", body = BaseResponse),
+        (status = 422, description = "Invalid settings", body = ValidationResponse)
+    ),
+    description = "Replaces the settings of the lobby with the specified id while it is waiting \
+    for players and notifies all users via websocket with the message \
+    {\"type\": \"settings_changed\", \"value\": settings}"
+)]
+pub async fn lobby_settings_handler(
+    State(state): State<SharedAppState>,
+    Json(payload): Json<LobbySettingsDTO>,
+) -> impl IntoResponse {
+    let errors = validate_settings(&payload.settings);
+    if !errors.is_empty() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ValidationResponse {
+                success: false,
+                message: Some("Invalid settings".to_string()),
+                errors,
+            })
+            .into_response(),
+        );
+    }
+    if payload.settings.moderation != ModerationMode::Off {
+        if let Err(err) = load_word_lists(DB.get().await).await {
+            event!(Level::ERROR, "Failed to load word lists: {}", err);
+        }
+    }
+    let exclusive_state = state.read().unwrap();
     let lobby = exclusive_state.get(&payload.name);
     if lobby.is_none() {
         return (
@@ -349,13 +1902,211 @@ pub async fn start_fill_handler(
             .into_response(),
         );
     }
-    let mut lobby = lobby.unwrap().write().unwrap();
-    lobby.game.view = "fill".to_string();
-    // notify all users that the filling process has started
-    let _ = lobby
-        .game
-        .tx
-        .send(to_string(&WSMessage::change_view("fill".to_string())).unwrap());
+    let mut lobby = lobby.unwrap().write().unwrap();
+    if lobby.game.view != "waiting" {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Settings can't be changed anymore".to_string()),
+            })
+            .into_response(),
+        );
+    }
+    lobby.settings = payload.settings.clone();
+    // notify all users about the new settings
+    lobby
+        .game
+        .connections
+        .send_all(ServerMessage::SettingsChanged(payload.settings.clone()));
+    (
+        StatusCode::OK,
+        Json(BaseResponse {
+            success: true,
+            message: None,
+        })
+        .into_response(),
+    )
+}
+
+// Url: /api/admin/review
+// Returns the answers of a lobby for the host to review before guessing starts
+// Method: GET
+// Response: Vec<ReviewGapDTO>
+#[utoipa::path(
+    get,
+    path = "/api/admin/review",
+    params(
+        ("name" = String, Query, description = "ID of the lobby")
+    ),
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    responses(
+        (status = 200, description = "Answers retrieved successfully", body = [ReviewGapDTO]),
+        (status = 400, description = "Game is not in review", body = BaseResponse),
+        (status = 404, description = "Game not found", body = BaseResponse)
+    )
+)]
+pub async fn review_queue_handler(
+    State(state): State<SharedAppState>,
+    Query(query): Query<BaseStringDTO>,
+) -> impl IntoResponse {
+    let exclusive_state = state.read().unwrap();
+    let lobby = exclusive_state.get(&query.name);
+    if lobby.is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Game not found".to_string()),
+            })
+            .into_response(),
+        );
+    }
+    let lobby = lobby.unwrap().read().unwrap();
+    if lobby.game.view != "review" {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Game is not in review".to_string()),
+            })
+            .into_response(),
+        );
+    }
+    let users = lobby.users.read().unwrap();
+    let queue: Vec<ReviewGapDTO> = lobby
+        .game
+        .gaps
+        .iter()
+        .filter_map(|g| {
+            let g = g.read().unwrap();
+            g.gap_after.then(|| ReviewGapDTO {
+                gap_id: g.id,
+                text: g.text_section.clone(),
+                value: g.value.clone(),
+                filled: g.filled,
+                filled_by: users
+                    .iter()
+                    .find(|u| g.filled_by.as_ref() == Some(&u.token))
+                    .map(|u| u.name.clone()),
+                status: g.review.clone(),
+            })
+        })
+        .collect();
+    (StatusCode::OK, Json(queue).into_response())
+}
+
+// Url: /api/admin/review
+// Approves, hides or returns an answer of a lobby in review, guessing starts once all answers
+// are approved or hidden
+// Method: POST
+// Request: ReviewActionDTO{name: String, gap_id: u32, action: String}
+// Response: BaseResponse
+#[utoipa::path(
+    post,
+    path = "/api/admin/review",
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    request_body = ReviewActionDTO,
+    responses(
+        (status = 200, description = "Answer reviewed", body = BaseResponse),
+        (status = 400, description = "Game is not in review, gap not filled or invalid action", body = BaseResponse),
+        (status = 404, description = "Game not found | Gap not found", body = BaseResponse)
+    ),
+    description = "Reviews the answer of a gap with the action `approve`, `hide` (the answer is \
+    not revealed) or `return` (the author has to rewrite it, all users are notified via websocket \
+    with the message {\"type\": \"answer_returned\", \"value\": gap_id}). Guessing starts \
+    once all answers are approved or hidden."
+)]
+pub async fn review_answer_handler(
+    State(state): State<SharedAppState>,
+    Json(payload): Json<ReviewActionDTO>,
+) -> impl IntoResponse {
+    let exclusive_state = state.read().unwrap();
+    let opt_lobby = exclusive_state.get(&payload.name);
+    if opt_lobby.is_none() {
+        return (
+            StatusCode::NOT_FOUND,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Game not found".to_string()),
+            })
+            .into_response(),
+        );
+    }
+    let review_done = {
+        let lobby = opt_lobby.unwrap().read().unwrap();
+        if lobby.game.view != "review" {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("Game is not in review".to_string()),
+                })
+                .into_response(),
+            );
+        }
+        let opt_gap = lobby
+            .game
+            .gaps
+            .get(payload.gap_id as usize)
+            .filter(|g| g.read().unwrap().gap_after);
+        if opt_gap.is_none() {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("Gap not found".to_string()),
+                })
+                .into_response(),
+            );
+        }
+        {
+            let mut gap = opt_gap.unwrap().write().unwrap();
+            if payload.action != "hide" && !gap.filled {
+                return (
+                    StatusCode::BAD_REQUEST,
+                    Json(BaseResponse {
+                        success: false,
+                        message: Some("Gap not filled".to_string()),
+                    })
+                    .into_response(),
+                );
+            }
+            match payload.action.as_str() {
+                "approve" => gap.review = ReviewStatus::Approved,
+                "hide" => gap.review = ReviewStatus::Hidden,
+                "return" => {
+                    gap.review = ReviewStatus::Returned;
+                    gap.filled = false;
+                    gap.value = "".to_string();
+                    lobby
+                        .game
+                        .connections
+                        .send_all(ServerMessage::AnswerReturned(payload.gap_id));
+                }
+                _ => {
+                    return (
+                        StatusCode::BAD_REQUEST,
+                        Json(BaseResponse {
+                            success: false,
+                            message: Some("Action must be approve, hide or return".to_string()),
+                        })
+                        .into_response(),
+                    );
+                }
+            }
+        }
+        lobby.review_done()
+    };
+    if review_done {
+        start_guessing(&state, &payload.name, opt_lobby.unwrap());
+    }
     (
         StatusCode::OK,
         Json(BaseResponse {
@@ -365,3 +2116,457 @@ pub async fn start_fill_handler(
         .into_response(),
     )
 }
+
+// Url: /api/admin/wordlists
+// Returns the blocked word lists uploaded by admins
+// Method: GET
+// Response: Vec<WordListDTO>
+#[utoipa::path(
+    get,
+    path = "/api/admin/wordlists",
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    responses(
+        (status = 200, description = "Word lists retrieved successfully", body = [WordListDTO]),
+        (status = 500, description = "Failed to get word lists", body = BaseResponse)
+    ),
+    description = "Returns the uploaded word lists, the built-in lists are not included"
+)]
+pub async fn word_lists_handler() -> impl IntoResponse {
+    let con: &Surreal<Client> = DB.get().await;
+    let lists: surrealdb::Result<Vec<WordListDTO>> = con.select("word_list").await;
+    match lists {
+        Ok(lists) => (StatusCode::OK, Json(lists).into_response()),
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Failed to get word lists".to_string()),
+            })
+            .into_response(),
+        ),
+    }
+}
+
+// Url: /api/admin/wordlists
+// Stores the blocked words of a language, replacing a previously uploaded list
+// Method: POST
+// Request: WordListDTO{language: String, words: Vec<String>}
+// Response: WordListDTO
+#[utoipa::path(
+    post,
+    path = "/api/admin/wordlists",
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    request_body = WordListDTO,
+    responses(
+        (status = 200, description = "Word list stored", body = WordListDTO),
+        (status = 422, description = "Invalid word list", body = ValidationResponse),
+        (status = 500, description = "Failed to store word list", body = BaseResponse)
+    ),
+    description = "Stores the blocked words of a language in addition to the built-in ones. Words \
+    are lowercased and deduplicated, leetspeak spellings of them are matched automatically."
+)]
+pub async fn upload_word_list_handler(Json(payload): Json<WordListDTO>) -> impl IntoResponse {
+    let errors = validate_word_list(&payload);
+    if !errors.is_empty() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ValidationResponse {
+                success: false,
+                message: Some("Invalid word list".to_string()),
+                errors,
+            })
+            .into_response(),
+        );
+    }
+    let mut words: Vec<String> = payload
+        .words
+        .iter()
+        .map(|w| sanitize_text(w).to_lowercase())
+        .collect();
+    words.sort();
+    words.dedup();
+    let list = WordListDTO {
+        language: payload.language.to_lowercase(),
+        words,
+    };
+
+    let con: &Surreal<Client> = DB.get().await;
+    let stored: surrealdb::Result<Option<WordListDTO>> = con
+        .upsert(("word_list", list.language.clone()))
+        .content(list)
+        .await;
+    match stored {
+        Ok(Some(list)) => {
+            cache_word_list(&list.language, Some(list.words.clone()));
+            (StatusCode::OK, Json(list).into_response())
+        }
+        _ => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Failed to store word list".to_string()),
+            })
+            .into_response(),
+        ),
+    }
+}
+
+// Url: /api/admin/wordlists/{language}
+// Deletes the uploaded blocked words of a language
+// Method: DELETE
+// Response: BaseResponse
+#[utoipa::path(
+    delete,
+    path = "/api/admin/wordlists/{language}",
+    params(
+        ("language" = String, Path, description = "Language code of the word list")
+    ),
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    responses(
+        (status = 200, description = "Word list deleted", body = BaseResponse),
+        (status = 404, description = "No word list found", body = BaseResponse),
+        (status = 500, description = "Failed to delete word list", body = BaseResponse)
+    )
+)]
+pub async fn delete_word_list_handler(Path(language): Path<String>) -> impl IntoResponse {
+    let language = language.to_lowercase();
+    let con: &Surreal<Client> = DB.get().await;
+    let deleted: surrealdb::Result<Option<WordListDTO>> =
+        con.delete(("word_list", language.clone())).await;
+    match deleted {
+        Ok(Some(_)) => {
+            cache_word_list(&language, None);
+            (
+                StatusCode::OK,
+                Json(BaseResponse {
+                    success: true,
+                    message: None,
+                })
+                .into_response(),
+            )
+        }
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(BaseResponse {
+                success: false,
+                message: Some("No word list found".to_string()),
+            })
+            .into_response(),
+        ),
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Failed to delete word list".to_string()),
+            })
+            .into_response(),
+        ),
+    }
+}
+
+// Url: /api/admin/keys
+// Returns all api keys without their secrets
+// Method: GET
+// Response: Vec<ApiKeyInfoDTO>
+#[utoipa::path(
+    get,
+    path = "/api/admin/keys",
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    responses(
+        (status = 200, description = "Api keys retrieved successfully", body = [ApiKeyInfoDTO]),
+        (status = 500, description = "Failed to get api keys", body = BaseResponse)
+    )
+)]
+pub async fn api_keys_handler() -> impl IntoResponse {
+    let con: &Surreal<Client> = DB.get().await;
+    let keys: surrealdb::Result<Vec<ApiKeyDTO>> = con.select("api_key").await;
+    match keys {
+        Ok(keys) => (
+            StatusCode::OK,
+            Json(
+                keys.into_iter()
+                    .map(ApiKeyInfoDTO::from)
+                    .collect::<Vec<ApiKeyInfoDTO>>(),
+            )
+            .into_response(),
+        ),
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Failed to get api keys".to_string()),
+            })
+            .into_response(),
+        ),
+    }
+}
+
+// Url: /api/admin/keys
+// Creates a new api key with the specified scopes
+// Method: POST
+// Request: CreateApiKeyDTO{name: String, scopes: Vec<String>, expires_in: Option<u64>}
+// Response: ApiKeyCreatedDTO
+#[utoipa::path(
+    post,
+    path = "/api/admin/keys",
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    request_body = CreateApiKeyDTO,
+    responses(
+        (status = 200, description = "Api key created", body = ApiKeyCreatedDTO),
+        (status = 422, description = "Invalid name, scopes or expiry", body = ValidationResponse),
+        (status = 500, description = "Failed to create api key", body = BaseResponse)
+    ),
+    description = "Creates a new api key. The key is only returned once and has to be sent in \
+    the `apikey` header. Only a hash of it is stored."
+)]
+pub async fn create_api_key_handler(Json(payload): Json<CreateApiKeyDTO>) -> impl IntoResponse {
+    let errors = validate_api_key(&payload);
+    if !errors.is_empty() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ValidationResponse {
+                success: false,
+                message: Some("Invalid api key".to_string()),
+                errors,
+            })
+            .into_response(),
+        );
+    }
+    let key_id = generate_key_id();
+    let secret = generate_secret();
+    let key = format_key(&key_id, &secret);
+    let now = unix_timestamp();
+    let mut scopes = payload.scopes.clone();
+    scopes.sort();
+    scopes.dedup();
+    let record = ApiKeyDTO {
+        key_id: key_id.clone(),
+        name: payload.name.trim().to_string(),
+        hash: hash_secret(&secret),
+        scopes,
+        created_at: now,
+        expires_at: payload.expires_in.map(|seconds| now + seconds),
+        revoked: false,
+        last_used_at: None,
+    };
+    let con: &Surreal<Client> = DB.get().await;
+    let created: surrealdb::Result<Option<ApiKeyDTO>> =
+        con.create(("api_key", key_id)).content(record).await;
+    match created {
+        Ok(Some(created)) => (
+            StatusCode::OK,
+            Json(ApiKeyCreatedDTO {
+                key,
+                info: created.into(),
+            })
+            .into_response(),
+        ),
+        _ => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Failed to create api key".to_string()),
+            })
+            .into_response(),
+        ),
+    }
+}
+
+// Url: /api/admin/keys/{key_id}/rotate
+// Replaces the secret of an api key, the old key stops working immediately
+// Method: POST
+// Response: ApiKeyCreatedDTO
+#[utoipa::path(
+    post,
+    path = "/api/admin/keys/{key_id}/rotate",
+    params(
+        ("key_id" = String, Path, description = "ID of the api key")
+    ),
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    responses(
+        (status = 200, description = "Api key rotated", body = ApiKeyCreatedDTO),
+        (status = 400, description = "Api key revoked", body = BaseResponse),
+        (status = 404, description = "No api key found", body = BaseResponse),
+        (status = 500, description = "Failed to rotate api key", body = BaseResponse)
+    ),
+    description = "Replaces the secret of an api key and unlocks it, keeping its name, scopes and \
+    expiry. The new key is only returned once."
+)]
+pub async fn rotate_api_key_handler(Path(key_id): Path<String>) -> impl IntoResponse {
+    let con: &Surreal<Client> = DB.get().await;
+    let stored: surrealdb::Result<Option<ApiKeyDTO>> =
+        con.select(("api_key", key_id.clone())).await;
+    let stored = match stored {
+        Ok(Some(stored)) => stored,
+        Ok(None) => {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("No api key found".to_string()),
+                })
+                .into_response(),
+            );
+        }
+        Err(_) => {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("Failed to rotate api key".to_string()),
+                })
+                .into_response(),
+            );
+        }
+    };
+    if stored.revoked {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Api key revoked".to_string()),
+            })
+            .into_response(),
+        );
+    }
+    let secret = generate_secret();
+    let key = format_key(&key_id, &secret);
+    let record = ApiKeyDTO {
+        hash: hash_secret(&secret),
+        ..stored
+    };
+    let updated: surrealdb::Result<Option<ApiKeyDTO>> =
+        con.update(("api_key", key_id)).content(record).await;
+    match updated {
+        Ok(Some(updated)) => (
+            StatusCode::OK,
+            Json(ApiKeyCreatedDTO {
+                key,
+                info: updated.into(),
+            })
+            .into_response(),
+        ),
+        _ => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Failed to rotate api key".to_string()),
+            })
+            .into_response(),
+        ),
+    }
+}
+
+// Url: /api/admin/keys/{key_id}/revoke
+// Revokes an api key, revoked keys are kept for auditing
+// Method: POST
+// Response: BaseResponse
+#[utoipa::path(
+    post,
+    path = "/api/admin/keys/{key_id}/revoke",
+    params(
+        ("key_id" = String, Path, description = "ID of the api key")
+    ),
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    responses(
+        (status = 200, description = "Api key revoked", body = BaseResponse),
+        (status = 404, description = "No api key found", body = BaseResponse),
+        (status = 500, description = "Failed to revoke api key", body = BaseResponse)
+    )
+)]
+pub async fn revoke_api_key_handler(Path(key_id): Path<String>) -> impl IntoResponse {
+    let con: &Surreal<Client> = DB.get().await;
+    let updated: surrealdb::Result<Option<ApiKeyDTO>> = con
+        .update(("api_key", key_id))
+        .merge(serde_json::json!({ "revoked": true }))
+        .await;
+    match updated {
+        Ok(Some(_)) => (
+            StatusCode::OK,
+            Json(BaseResponse {
+                success: true,
+                message: None,
+            })
+            .into_response(),
+        ),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(BaseResponse {
+                success: false,
+                message: Some("No api key found".to_string()),
+            })
+            .into_response(),
+        ),
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Failed to revoke api key".to_string()),
+            })
+            .into_response(),
+        ),
+    }
+}
+
+// Url: /api/admin/tokens/revoke
+// Revokes all bearer and refresh tokens issued before now
+// Method: POST
+// Response: BaseResponse
+#[utoipa::path(
+    post,
+    path = "/api/admin/tokens/revoke",
+    security(
+        ("ApiKey" = []),
+        ("Bearer" = [])
+    ),
+    responses(
+        (status = 200, description = "Tokens revoked", body = BaseResponse),
+        (status = 500, description = "Failed to revoke tokens", body = BaseResponse)
+    ),
+    description = "Ends all sessions of the login, including the one of the caller. Api keys keep \
+    working and can be used to log in again."
+)]
+pub async fn revoke_tokens_handler() -> impl IntoResponse {
+    match revoke_tokens().await {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(BaseResponse {
+                success: true,
+                message: None,
+            })
+            .into_response(),
+        ),
+        Err(err) => {
+            event!(Level::ERROR, "Failed to revoke tokens: {}", err);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("Failed to revoke tokens".to_string()),
+                })
+                .into_response(),
+            )
+        }
+    }
+}
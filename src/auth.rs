@@ -0,0 +1,479 @@
+use crate::db::DB;
+use crate::dto::{ApiKeyDTO, TokenResponseDTO};
+use crate::utils::{id_generator, unix_timestamp};
+use axum::http::StatusCode;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use lazy_static::lazy_static;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use subtle::ConstantTimeEq;
+use surrealdb::engine::remote::ws::Client;
+use surrealdb::Surreal;
+use tracing::{event, Level};
+
+pub const TEMPLATES_READ: &str = "templates:read";
+pub const TEMPLATES_WRITE: &str = "templates:write";
+pub const LOBBIES_MANAGE: &str = "lobbies:manage";
+pub const KEYS_MANAGE: &str = "keys:manage";
+pub const SCOPES: [&str; 4] = [TEMPLATES_READ, TEMPLATES_WRITE, LOBBIES_MANAGE, KEYS_MANAGE];
+
+const KEY_PREFIX: &str = "ww";
+const KEY_ID_LENGTH: usize = 8;
+const SECRET_LENGTH: usize = 32;
+// A key is locked for an ip for LOCKOUT_SECONDS after MAX_FAILED_ATTEMPTS wrong secrets in a row
+const MAX_FAILED_ATTEMPTS: u32 = 5;
+const LOCKOUT_SECONDS: u64 = 15 * 60;
+// Password logins are rejected for all ips once this many failed within a minute
+const MAX_FAILED_LOGINS_PER_MINUTE: u32 = 20;
+// last_used_at is only written once per interval to save db writes
+const LAST_USED_INTERVAL: u64 = 60;
+
+type HmacSha256 = Hmac<Sha256>;
+
+lazy_static! {
+    // Key from the environment that has all scopes, used to create the first keys
+    static ref BOOTSTRAP_KEY: Option<String> =
+        std::env::var("AUTH_KEY").ok().filter(|k| !k.is_empty());
+    // Password for the login of the host UI, the login with a password is disabled if not set
+    static ref ADMIN_PASSWORD: Option<String> =
+        std::env::var("ADMIN_PASSWORD").ok().filter(|p| !p.is_empty());
+    // Secret to sign tokens with, tokens don't survive a restart if it is not set
+    static ref TOKEN_SECRET: Vec<u8> = match std::env::var("TOKEN_SECRET") {
+        Ok(secret) if !secret.is_empty() => secret.into_bytes(),
+        _ => {
+            event!(Level::WARN, "TOKEN_SECRET not set, using a random secret");
+            generate_secret().into_bytes()
+        }
+    };
+    static ref ACCESS_TOKEN_TTL: u64 = std::env::var("ACCESS_TOKEN_TTL")
+        .ok()
+        .and_then(|ttl| ttl.parse().ok())
+        .unwrap_or(15 * 60);
+    static ref REFRESH_TOKEN_TTL: u64 = std::env::var("REFRESH_TOKEN_TTL")
+        .ok()
+        .and_then(|ttl| ttl.parse().ok())
+        .unwrap_or(7 * 24 * 60 * 60);
+    // Failed password logins and the time until which the login is locked per ip
+    static ref FAILED_LOGINS: Mutex<HashMap<IpAddr, (u32, u64)>> = Mutex::new(HashMap::new());
+    // Failed password logins of all ips in the current minute and its start
+    static ref FAILED_LOGINS_TOTAL: Mutex<(u32, u64)> = Mutex::new((0, 0));
+    // Tokens issued up to this unix timestamp are revoked, loaded from the db on refresh
+    static ref REVOKED_BEFORE: AtomicU64 = AtomicU64::new(0);
+}
+
+// Who is authenticated by an api key, password or token
+pub struct Identity {
+    pub subject: String, // "key:<id>", "bootstrap" for the bootstrap key or "admin" for the password
+    pub scopes: Vec<String>,
+}
+
+impl Identity {
+    fn admin(subject: &str) -> Self {
+        Identity {
+            subject: subject.to_string(),
+            scopes: SCOPES.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct TokenClaims {
+    sub: String,
+    scopes: Vec<String>,
+    kind: String, // access or refresh
+    #[serde(default)]
+    iat: u64, // unix timestamp in seconds
+    exp: u64,     // unix timestamp in seconds
+}
+
+pub fn hash_secret(secret: &str) -> String {
+    hex::encode(Sha256::digest(secret.as_bytes()))
+}
+
+pub fn generate_secret() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(SECRET_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+pub fn generate_key_id() -> String {
+    id_generator(KEY_ID_LENGTH).to_lowercase()
+}
+
+// Api keys are handed out as `ww_<id>_<secret>`
+pub fn format_key(key_id: &str, secret: &str) -> String {
+    format!("{}_{}_{}", KEY_PREFIX, key_id, secret)
+}
+
+fn parse_key(key: &str) -> Option<(&str, &str)> {
+    let mut parts = key.splitn(3, '_');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(KEY_PREFIX), Some(id), Some(secret)) if !id.is_empty() && !secret.is_empty() => {
+            Some((id, secret))
+        }
+        _ => None,
+    }
+}
+
+async fn update_key(con: &Surreal<Client>, key_id: &str, data: serde_json::Value) {
+    let updated: surrealdb::Result<Option<ApiKeyDTO>> = con
+        .update(("api_key", key_id.to_string()))
+        .merge(data)
+        .await;
+    if let Err(err) = updated {
+        event!(Level::ERROR, "Failed to update api key {}: {}", key_id, err);
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyLockout {
+    failed_attempts: u32,
+    locked_until: Option<u64>,
+}
+
+// Wrong secrets are counted per key and ip in the `api_key_lockout` table, so a client can't
+// lock a key for everyone else by sending wrong secrets for its public id
+fn lockout_id(key_id: &str, ip: IpAddr) -> String {
+    format!("{}_{}", key_id, ip)
+}
+
+async fn load_lockout(
+    con: &Surreal<Client>,
+    id: &str,
+) -> Result<Option<KeyLockout>, (StatusCode, String)> {
+    let stored: surrealdb::Result<Option<KeyLockout>> =
+        con.select(("api_key_lockout", id.to_string())).await;
+    stored.map_err(|err| {
+        event!(Level::ERROR, "Failed to load api key lockout: {}", err);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to check api key".to_string(),
+        )
+    })
+}
+
+// Counts a wrong secret in a single query, so concurrent failures are all counted, and locks the
+// key for the ip once there were too many
+async fn record_failed_attempt(con: &Surreal<Client>, id: &str, now: u64) {
+    let counted: surrealdb::Result<Option<KeyLockout>> = async {
+        con.query(
+            "UPSERT type::thing('api_key_lockout', $id) SET failed_attempts += 1 RETURN AFTER",
+        )
+        .bind(("id", id.to_string()))
+        .await?
+        .take(0)
+    }
+    .await;
+    match counted {
+        Ok(Some(lockout)) if lockout.failed_attempts >= MAX_FAILED_ATTEMPTS => {
+            event!(Level::WARN, "Api key lockout {} locked", id);
+            let locked: surrealdb::Result<Option<KeyLockout>> = con
+                .update(("api_key_lockout", id.to_string()))
+                .merge(json!({ "failed_attempts": 0, "locked_until": now + LOCKOUT_SECONDS }))
+                .await;
+            if let Err(err) = locked {
+                event!(Level::ERROR, "Failed to lock api key {}: {}", id, err);
+            }
+        }
+        Ok(_) => {}
+        Err(err) => event!(
+            Level::ERROR,
+            "Failed to count failed attempt {}: {}",
+            id,
+            err
+        ),
+    }
+}
+
+// Checks an api key sent from an ip and returns the subject and scopes it authenticates, on
+// failure the status code and message for the response are returned
+pub async fn authenticate_api_key(key: &str, ip: IpAddr) -> Result<Identity, (StatusCode, String)> {
+    let unauthorized = |message: &str| Err((StatusCode::UNAUTHORIZED, message.to_string()));
+    if let Some(bootstrap) = BOOTSTRAP_KEY.as_ref() {
+        if bool::from(key.as_bytes().ct_eq(bootstrap.as_bytes())) {
+            return Ok(Identity::admin("bootstrap"));
+        }
+    }
+    let Some((key_id, secret)) = parse_key(key) else {
+        return unauthorized("Unauthorized");
+    };
+
+    let con: &Surreal<Client> = DB.get().await;
+    let stored = load_key(con, key_id).await?;
+    let Some(stored) = stored else {
+        return unauthorized("Unauthorized");
+    };
+    let now = unix_timestamp();
+    let lockout_id = lockout_id(key_id, ip);
+    let lockout = load_lockout(con, &lockout_id).await?;
+    if lockout
+        .as_ref()
+        .is_some_and(|l| l.locked_until.is_some_and(|until| until > now))
+    {
+        return unauthorized("Api key locked after too many failed attempts");
+    }
+    let hash = hash_secret(secret);
+    if !bool::from(hash.as_bytes().ct_eq(stored.hash.as_bytes())) {
+        record_failed_attempt(con, &lockout_id, now).await;
+        return unauthorized("Unauthorized");
+    }
+    check_key_valid(&stored, now)?;
+    if lockout.is_some() {
+        let deleted: surrealdb::Result<Option<KeyLockout>> =
+            con.delete(("api_key_lockout", lockout_id.clone())).await;
+        if let Err(err) = deleted {
+            event!(
+                Level::ERROR,
+                "Failed to reset api key lockout {}: {}",
+                lockout_id,
+                err
+            );
+        }
+    }
+    if stored
+        .last_used_at
+        .is_none_or(|used| now >= used + LAST_USED_INTERVAL)
+    {
+        update_key(con, key_id, json!({ "last_used_at": now })).await;
+    }
+    Ok(Identity {
+        subject: format!("key:{}", key_id),
+        scopes: stored.scopes,
+    })
+}
+
+async fn load_key(
+    con: &Surreal<Client>,
+    key_id: &str,
+) -> Result<Option<ApiKeyDTO>, (StatusCode, String)> {
+    let stored: surrealdb::Result<Option<ApiKeyDTO>> =
+        con.select(("api_key", key_id.to_string())).await;
+    stored.map_err(|err| {
+        event!(Level::ERROR, "Failed to load api key: {}", err);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to check api key".to_string(),
+        )
+    })
+}
+
+fn check_key_valid(key: &ApiKeyDTO, now: u64) -> Result<(), (StatusCode, String)> {
+    if key.revoked {
+        return Err((StatusCode::UNAUTHORIZED, "Api key revoked".to_string()));
+    }
+    if key.expires_at.is_some_and(|expires| expires <= now) {
+        return Err((StatusCode::UNAUTHORIZED, "Api key expired".to_string()));
+    }
+    Ok(())
+}
+
+fn check_scope(identity: &Identity, scope: &str) -> Result<(), (StatusCode, String)> {
+    if identity.scopes.iter().any(|s| s == scope) {
+        Ok(())
+    } else {
+        Err((StatusCode::FORBIDDEN, format!("Missing scope {}", scope)))
+    }
+}
+
+// Checks an api key and whether it has the required scope
+pub async fn verify_api_key(
+    key: &str,
+    ip: IpAddr,
+    scope: &str,
+) -> Result<(), (StatusCode, String)> {
+    let identity = authenticate_api_key(key, ip).await?;
+    check_scope(&identity, scope)
+}
+
+// Checks the admin password, repeated failures lock the login for the ip and too many failures
+// of all ips lock it for everyone until the minute is over
+pub fn authenticate_password(password: &str, ip: IpAddr) -> Result<Identity, (StatusCode, String)> {
+    let now = unix_timestamp();
+    let mut failed_logins = FAILED_LOGINS.lock().unwrap();
+    let mut failed_total = FAILED_LOGINS_TOTAL.lock().unwrap();
+    if now >= failed_total.1 + 60 {
+        *failed_total = (0, now);
+    }
+    if failed_total.0 >= MAX_FAILED_LOGINS_PER_MINUTE
+        || failed_logins
+            .get(&ip)
+            .is_some_and(|(_, locked_until)| *locked_until > now)
+    {
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            "Too many failed logins, try again later".to_string(),
+        ));
+    }
+    let valid = ADMIN_PASSWORD.as_ref().is_some_and(|expected| {
+        bool::from(
+            hash_secret(password)
+                .as_bytes()
+                .ct_eq(hash_secret(expected).as_bytes()),
+        )
+    });
+    if valid {
+        failed_logins.remove(&ip);
+        return Ok(Identity::admin("admin"));
+    }
+    failed_total.0 += 1;
+    let (attempts, locked_until) = failed_logins.entry(ip).or_insert((0, 0));
+    *attempts += 1;
+    if *attempts >= MAX_FAILED_ATTEMPTS {
+        *attempts = 0;
+        *locked_until = now + LOCKOUT_SECONDS;
+    }
+    Err((StatusCode::UNAUTHORIZED, "Unauthorized".to_string()))
+}
+
+fn sign(payload: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(&TOKEN_SECRET).expect("hmac accepts any key length");
+    mac.update(payload.as_bytes());
+    URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+}
+
+// Tokens are `<base64 claims>.<base64 hmac of the claims>`
+fn encode_token(claims: &TokenClaims) -> String {
+    let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims).unwrap());
+    let signature = sign(&payload);
+    format!("{}.{}", payload, signature)
+}
+
+fn decode_token(token: &str, kind: &str) -> Result<TokenClaims, (StatusCode, String)> {
+    let invalid = || (StatusCode::UNAUTHORIZED, "Invalid token".to_string());
+    let (payload, signature) = token.split_once('.').ok_or_else(invalid)?;
+    if !bool::from(sign(payload).as_bytes().ct_eq(signature.as_bytes())) {
+        return Err(invalid());
+    }
+    let claims: TokenClaims = URL_SAFE_NO_PAD
+        .decode(payload)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+        .ok_or_else(invalid)?;
+    if claims.kind != kind {
+        return Err(invalid());
+    }
+    if claims.exp <= unix_timestamp() {
+        return Err((StatusCode::UNAUTHORIZED, "Token expired".to_string()));
+    }
+    if claims.iat < REVOKED_BEFORE.load(Ordering::Relaxed) {
+        return Err((StatusCode::UNAUTHORIZED, "Token revoked".to_string()));
+    }
+    Ok(claims)
+}
+
+#[derive(Serialize, Deserialize)]
+struct Revocation {
+    revoked_before: u64,
+}
+
+// Loads the time up to which tokens are revoked, so revocations survive restarts
+async fn load_revocation(con: &Surreal<Client>) -> Result<(), (StatusCode, String)> {
+    let stored: surrealdb::Result<Option<Revocation>> =
+        con.select(("token_revocation", "all")).await;
+    match stored {
+        Ok(stored) => {
+            if let Some(stored) = stored {
+                REVOKED_BEFORE.fetch_max(stored.revoked_before, Ordering::Relaxed);
+            }
+            Ok(())
+        }
+        Err(err) => {
+            event!(Level::ERROR, "Failed to load token revocation: {}", err);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to check token".to_string(),
+            ))
+        }
+    }
+}
+
+// Revokes all access and refresh tokens issued before now, everyone has to log in again
+pub async fn revoke_tokens() -> surrealdb::Result<()> {
+    let now = unix_timestamp();
+    let con: &Surreal<Client> = DB.get().await;
+    let _: Option<Revocation> = con
+        .upsert(("token_revocation", "all"))
+        .content(Revocation {
+            revoked_before: now,
+        })
+        .await?;
+    REVOKED_BEFORE.fetch_max(now, Ordering::Relaxed);
+    Ok(())
+}
+
+// Issues a short-lived access token and a refresh token for an authenticated identity
+pub fn issue_tokens(identity: Identity) -> TokenResponseDTO {
+    let now = unix_timestamp();
+    let access_token = encode_token(&TokenClaims {
+        sub: identity.subject.clone(),
+        scopes: identity.scopes.clone(),
+        kind: "access".to_string(),
+        iat: now,
+        exp: now + *ACCESS_TOKEN_TTL,
+    });
+    let refresh_token = encode_token(&TokenClaims {
+        sub: identity.subject,
+        scopes: identity.scopes,
+        kind: "refresh".to_string(),
+        iat: now,
+        exp: now + *REFRESH_TOKEN_TTL,
+    });
+    TokenResponseDTO {
+        access_token,
+        refresh_token,
+        token_type: "Bearer".to_string(),
+        expires_in: *ACCESS_TOKEN_TTL,
+    }
+}
+
+// Checks a bearer access token and whether it has the required scope, the revocation is loaded
+// first so revocations of other instances and from before a restart apply
+pub async fn verify_bearer_token(token: &str, scope: &str) -> Result<(), (StatusCode, String)> {
+    let con: &Surreal<Client> = DB.get().await;
+    load_revocation(con).await?;
+    let claims = decode_token(token, "access")?;
+    check_scope(
+        &Identity {
+            subject: claims.sub,
+            scopes: claims.scopes,
+        },
+        scope,
+    )
+}
+
+// Exchanges a refresh token for new tokens, tokens of api keys get the current scopes of the key
+// and stop working once the key is revoked or expired. Tokens of the password or bootstrap key
+// stop working once it is unset.
+pub async fn refresh_tokens(refresh_token: &str) -> Result<TokenResponseDTO, (StatusCode, String)> {
+    let con: &Surreal<Client> = DB.get().await;
+    load_revocation(con).await?;
+    let claims = decode_token(refresh_token, "refresh")?;
+    let unauthorized = || (StatusCode::UNAUTHORIZED, "Unauthorized".to_string());
+    let identity = match claims.sub.strip_prefix("key:") {
+        Some(key_id) => {
+            let stored = load_key(con, key_id).await?.ok_or_else(unauthorized)?;
+            check_key_valid(&stored, unix_timestamp())?;
+            Identity {
+                subject: claims.sub,
+                scopes: stored.scopes,
+            }
+        }
+        None if claims.sub == "admin" && ADMIN_PASSWORD.is_some() => Identity::admin("admin"),
+        None if claims.sub == "bootstrap" && BOOTSTRAP_KEY.is_some() => {
+            Identity::admin("bootstrap")
+        }
+        None => return Err(unauthorized()),
+    };
+    Ok(issue_tokens(identity))
+}
@@ -0,0 +1,181 @@
+use crate::dto::{GameDTO, GameMetadataDTO, GameVersionDTO, GapConstraintDTO};
+use serde::{Deserialize, Serialize};
+
+// Supported file formats for importing and exporting game templates
+pub const FORMATS: [&str; 3] = ["jsonl", "yaml", "csv"];
+
+// A game template as a single CSV record, the text uses the inline placeholder syntax
+#[derive(Serialize, Deserialize, Debug)]
+struct CsvGameRow {
+    name: String,
+    text: String,
+    description: Option<String>,
+    tags: Option<String>, // separated by ';'
+    language: Option<String>,
+    author: Option<String>,
+    age_rating: Option<u8>,
+    constraints: Option<String>, // JSON list of constraints
+}
+
+// Row number and the parsed template or an error message
+pub type ParsedRow = (usize, Result<GameDTO, String>);
+
+pub fn content_type(format: &str) -> &'static str {
+    match format {
+        "jsonl" => "application/jsonl",
+        "yaml" => "application/yaml",
+        _ => "text/csv",
+    }
+}
+
+// Parses a file of game templates into one row per template in the file
+pub fn parse_games(format: &str, body: &str) -> Result<Vec<ParsedRow>, String> {
+    match format {
+        "jsonl" => Ok(body
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| {
+                (
+                    i + 1,
+                    serde_json::from_str::<GameDTO>(line).map_err(|e| e.to_string()),
+                )
+            })
+            .collect()),
+        "yaml" => {
+            // parse the list first, so a broken entry doesn't fail the whole file
+            let entries: Vec<serde_norway::Value> =
+                serde_norway::from_str(body).map_err(|e| e.to_string())?;
+            Ok(entries
+                .into_iter()
+                .enumerate()
+                .map(|(i, entry)| {
+                    (
+                        i + 1,
+                        serde_norway::from_value::<GameDTO>(entry).map_err(|e| e.to_string()),
+                    )
+                })
+                .collect())
+        }
+        "csv" => {
+            let mut reader = csv::Reader::from_reader(body.as_bytes());
+            let headers = reader.headers().map_err(|e| e.to_string())?.clone();
+            Ok(reader
+                .records()
+                .enumerate()
+                .map(|(i, record)| {
+                    // rows are reported by the line they start on, quoted fields may span lines
+                    let line = match &record {
+                        Ok(record) => record.position().map(|p| p.line()),
+                        Err(e) => e.position().map(|p| p.line()),
+                    };
+                    let game = record
+                        .and_then(|r| r.deserialize::<CsvGameRow>(Some(&headers)))
+                        .map_err(|e| e.to_string())
+                        .and_then(game_from_csv_row);
+                    (line.map_or(i + 2, |l| l as usize), game)
+                })
+                .collect())
+        }
+        _ => Err(format!("Format must be one of {}", FORMATS.join(", "))),
+    }
+}
+
+fn game_from_csv_row(row: CsvGameRow) -> Result<GameDTO, String> {
+    let constraints: Vec<GapConstraintDTO> = match row.constraints {
+        Some(constraints) if !constraints.trim().is_empty() => {
+            serde_json::from_str(&constraints).map_err(|e| format!("constraints: {}", e))?
+        }
+        _ => vec![],
+    };
+    Ok(GameDTO {
+        name: row.name,
+        text: Some(row.text),
+        text_section: vec![],
+        hints: vec![],
+        constraints,
+        metadata: GameMetadataDTO {
+            description: row.description,
+            tags: row
+                .tags
+                .unwrap_or_default()
+                .split(';')
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty())
+                .collect(),
+            language: row.language,
+            author: row.author,
+            age_rating: row.age_rating,
+        },
+    })
+}
+
+// Joins text sections and hints back into the inline placeholder syntax
+fn render_placeholders(sections: &[String], hints: &[String]) -> String {
+    let mut text = String::new();
+    for (i, section) in sections.iter().enumerate() {
+        if i > 0 {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            let hint = hints.get(i - 1).map(|h| h.as_str()).unwrap_or("");
+            text.push_str(&format!("{{{{{}}}}}", hint));
+        }
+        if !section.is_empty() {
+            // no space between a placeholder and the punctuation following it
+            if !text.is_empty() && !section.starts_with(|c: char| c.is_ascii_punctuation()) {
+                text.push(' ');
+            }
+            text.push_str(section);
+        }
+    }
+    text
+}
+
+// Writes the latest versions of the game templates into a file of the specified format
+pub fn export_games(format: &str, games: Vec<GameVersionDTO>) -> Result<String, String> {
+    let games: Vec<GameDTO> = games
+        .into_iter()
+        .map(|g| GameDTO {
+            name: g.name,
+            text: None,
+            text_section: g.text_section,
+            hints: g.hints,
+            constraints: g.constraints,
+            metadata: g.metadata,
+        })
+        .collect();
+    match format {
+        "jsonl" => games
+            .iter()
+            .map(|g| serde_json::to_string(g).map(|line| line + "\n"))
+            .collect::<Result<String, _>>()
+            .map_err(|e| e.to_string()),
+        "yaml" => serde_norway::to_string(&games).map_err(|e| e.to_string()),
+        "csv" => {
+            let mut writer = csv::Writer::from_writer(vec![]);
+            for game in games {
+                let constraints = if game.constraints.is_empty() {
+                    None
+                } else {
+                    Some(serde_json::to_string(&game.constraints).map_err(|e| e.to_string())?)
+                };
+                writer
+                    .serialize(CsvGameRow {
+                        text: render_placeholders(&game.text_section, &game.hints),
+                        name: game.name,
+                        description: game.metadata.description,
+                        tags: Some(game.metadata.tags.join(";")),
+                        language: game.metadata.language,
+                        author: game.metadata.author,
+                        age_rating: game.metadata.age_rating,
+                        constraints,
+                    })
+                    .map_err(|e| e.to_string())?;
+            }
+            let bytes = writer.into_inner().map_err(|e| e.to_string())?;
+            String::from_utf8(bytes).map_err(|e| e.to_string())
+        }
+        _ => Err(format!("Format must be one of {}", FORMATS.join(", "))),
+    }
+}
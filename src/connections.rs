@@ -0,0 +1,263 @@
+use crate::ws_dto::{LobbyEvent, ServerMessage};
+use lazy_static::lazy_static;
+use serde_json::to_string;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{broadcast, mpsc};
+use tracing::{event, Level};
+
+lazy_static! {
+    // Lobby events that were not yet sent to a connection, connections that fall further
+    // behind miss the older ones and are sent a snapshot instead
+    pub static ref CHANNEL_CAPACITY: usize = std::env::var("WS_CHANNEL_CAPACITY")
+        .ok()
+        .and_then(|capacity| capacity.parse().ok())
+        .filter(|capacity| *capacity > 0)
+        .unwrap_or(100);
+}
+// Last lobby events kept to resend them to reconnecting clients
+const REPLAY_BUFFER_SIZE: usize = 256;
+
+// Message for all connections of a lobby, except the ones of the user with the token if set
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub seq: u64,
+    pub except: Option<String>,
+    pub message: String,
+}
+
+// What a connection has to be sent before the new lobby events
+pub enum Resume {
+    Events(Vec<Event>), // the missed events, still buffered
+    Snapshot,           // the missed events are not buffered anymore
+}
+
+// Receiving ends of a single websocket connection
+pub struct Connection {
+    pub id: u64,
+    pub events: broadcast::Receiver<Event>,
+    pub direct: mpsc::Receiver<String>, // closed once the user is disconnected
+    pub resume: Resume,
+}
+
+#[derive(Default)]
+struct History {
+    seq: u64, // sequence number of the last event, the first event has 1
+    events: VecDeque<Event>,
+}
+
+type Senders = Vec<(u64, mpsc::Sender<String>)>;
+
+// Websocket connections of a lobby by user token, a user may be connected more than once
+pub struct Connections {
+    tx: broadcast::Sender<Event>,
+    users: Mutex<HashMap<String, Senders>>,
+    next_id: AtomicU64,
+    history: Mutex<History>,
+}
+
+impl Default for Connections {
+    fn default() -> Self {
+        Connections {
+            tx: broadcast::channel(*CHANNEL_CAPACITY).0,
+            users: Mutex::new(HashMap::new()),
+            next_id: AtomicU64::new(0),
+            history: Mutex::new(History::default()),
+        }
+    }
+}
+
+impl Connections {
+    // Registers a new connection of the user, which resumes after the event with last_seq
+    pub fn connect(&self, token: &str, last_seq: Option<u64>) -> Connection {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let (direct_tx, direct) = mpsc::channel(*CHANNEL_CAPACITY);
+        self.users
+            .lock()
+            .unwrap()
+            .entry(token.to_string())
+            .or_default()
+            .push((id, direct_tx));
+        // subscribe while holding the history, so no event is missed or sent twice
+        let history = self.history.lock().unwrap();
+        let events = self.tx.subscribe();
+        let resume = match last_seq {
+            None => Resume::Events(Vec::new()),
+            Some(last) if last > history.seq => Resume::Snapshot,
+            Some(last)
+                if last == history.seq
+                    || history.events.front().is_some_and(|e| e.seq <= last + 1) =>
+            {
+                Resume::Events(
+                    history
+                        .events
+                        .iter()
+                        .filter(|e| e.seq > last && e.except.as_deref() != Some(token))
+                        .cloned()
+                        .collect(),
+                )
+            }
+            Some(_) => Resume::Snapshot,
+        };
+        Connection {
+            id,
+            events,
+            direct,
+            resume,
+        }
+    }
+
+    // Sequence number of the last lobby event
+    pub fn seq(&self) -> u64 {
+        self.history.lock().unwrap().seq
+    }
+
+    // Removes a connection after it was closed
+    pub fn disconnect(&self, token: &str, id: u64) {
+        let mut users = self.users.lock().unwrap();
+        if let Some(connections) = users.get_mut(token) {
+            connections.retain(|(c, _)| *c != id);
+            if connections.is_empty() {
+                users.remove(token);
+            }
+        }
+    }
+
+    // Closes all connections of the user, messages sent to the user before are still delivered
+    pub fn disconnect_user(&self, token: &str) {
+        self.users.lock().unwrap().remove(token);
+    }
+
+    pub fn is_connected(&self, token: &str) -> bool {
+        self.users.lock().unwrap().contains_key(token)
+    }
+
+    pub fn send_all(&self, message: ServerMessage) {
+        self.broadcast(None, message);
+    }
+
+    pub fn send_all_except(&self, token: &str, message: ServerMessage) {
+        self.broadcast(Some(token.to_string()), message);
+    }
+
+    // Sends a message to all connections of a single user, connections that have
+    // CHANNEL_CAPACITY messages to the user waiting already miss it
+    pub fn send_to(&self, token: &str, message: &ServerMessage) {
+        if let Some(connections) = self.users.lock().unwrap().get(token) {
+            let message = to_string(message).unwrap();
+            for (id, tx) in connections {
+                if let Err(TrySendError::Full(_)) = tx.try_send(message.clone()) {
+                    event!(
+                        Level::WARN,
+                        "Dropped message to connection {} of user {}, too many are waiting",
+                        id,
+                        token
+                    );
+                }
+            }
+        }
+    }
+
+    fn broadcast(&self, except: Option<String>, message: ServerMessage) {
+        let mut history = self.history.lock().unwrap();
+        history.seq += 1;
+        let seq = history.seq;
+        let event = Event {
+            seq,
+            except,
+            message: to_string(&LobbyEvent { message, seq }).unwrap(),
+        };
+        if history.events.len() == REPLAY_BUFFER_SIZE {
+            history.events.pop_front();
+        }
+        history.events.push_back(event.clone());
+        // sending only fails if nobody is connected
+        let _ = self.tx.send(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connections_with_events(count: u32) -> Connections {
+        let connections = Connections::default();
+        for i in 0..count {
+            connections.send_all(ServerMessage::GapClaimed(i));
+        }
+        connections
+    }
+
+    fn resumed_seqs(connections: &Connections, last_seq: Option<u64>) -> Option<Vec<u64>> {
+        match connections.connect("token", last_seq).resume {
+            Resume::Events(events) => Some(events.iter().map(|e| e.seq).collect()),
+            Resume::Snapshot => None,
+        }
+    }
+
+    #[test]
+    fn new_connections_get_no_missed_events() {
+        let connections = connections_with_events(3);
+        assert_eq!(resumed_seqs(&connections, None), Some(vec![]));
+    }
+
+    #[test]
+    fn resumes_at_the_last_event() {
+        let connections = connections_with_events(300);
+        assert_eq!(connections.seq(), 300);
+        assert_eq!(resumed_seqs(&connections, Some(300)), Some(vec![]));
+        assert_eq!(resumed_seqs(&connections, Some(299)), Some(vec![300]));
+    }
+
+    #[test]
+    fn resumes_at_the_start_of_the_buffer() {
+        // the buffer holds the events 45 to 300
+        let connections = connections_with_events(300);
+        let first = 300 - REPLAY_BUFFER_SIZE as u64 + 1;
+        let resumed = resumed_seqs(&connections, Some(first - 1)).unwrap();
+        assert_eq!(resumed.len(), REPLAY_BUFFER_SIZE);
+        assert_eq!(resumed.first(), Some(&first));
+        assert_eq!(resumed.last(), Some(&300));
+    }
+
+    #[test]
+    fn sends_a_snapshot_before_the_buffer() {
+        let connections = connections_with_events(300);
+        let first = 300 - REPLAY_BUFFER_SIZE as u64 + 1;
+        assert_eq!(resumed_seqs(&connections, Some(first - 2)), None);
+        assert_eq!(resumed_seqs(&connections, Some(0)), None);
+    }
+
+    #[test]
+    fn sends_a_snapshot_after_the_last_event() {
+        let connections = connections_with_events(10);
+        assert_eq!(resumed_seqs(&connections, Some(11)), None);
+    }
+
+    #[test]
+    fn resumes_from_the_first_event_before_the_buffer_is_full() {
+        let connections = connections_with_events(10);
+        assert_eq!(
+            resumed_seqs(&connections, Some(0)),
+            Some((1..=10).collect())
+        );
+    }
+
+    #[test]
+    fn skips_events_not_meant_for_the_user() {
+        let connections = connections_with_events(2);
+        connections.send_all_except("token", ServerMessage::GapClaimed(2));
+        connections.send_all(ServerMessage::GapClaimed(3));
+        assert_eq!(resumed_seqs(&connections, Some(1)), Some(vec![2, 4]));
+    }
+
+    #[test]
+    fn receives_events_after_connecting() {
+        let connections = connections_with_events(5);
+        let mut connection = connections.connect("token", Some(5));
+        connections.send_all(ServerMessage::GapClaimed(5));
+        assert_eq!(connection.events.try_recv().unwrap().seq, 6);
+    }
+}
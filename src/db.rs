@@ -19,8 +19,8 @@ lazy_static! {
                 .expect("couldn't connect to surrealdb");
 
             db.signin(Root {
-                username: &*username,
-                password: &*password,
+                username: &username,
+                password: &password,
             })
             .await
             .expect("couldn't sign in");
@@ -30,7 +30,14 @@ lazy_static! {
                 .await
                 .expect("could not use ns and db");
 
+            // every version of a template is stored once
+            db.query("DEFINE INDEX IF NOT EXISTS game_version_name_version ON TABLE game_version FIELDS name, version UNIQUE")
+                .await
+                .expect("could not define indexes")
+                .check()
+                .expect("could not define indexes");
+
             db
         })
     };
-}
\ No newline at end of file
+}
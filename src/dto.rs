@@ -1,26 +1,298 @@
-use serde::{Deserialize, Serialize};
-use surrealdb::RecordId;
+use crate::ws_dto::{GuessScore, TeamScore};
+use serde::{Deserialize, Deserializer, Serialize};
 use utoipa::ToSchema;
-use crate::ws_dto::GuessScore;
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AnswerType {
+    SingleWord,
+    #[default]
+    FreeText,
+}
+
+fn default_required() -> bool {
+    true
+}
+
+// Constraints an answer for a gap has to fulfill, lengths are counted in characters
+#[derive(Deserialize, Serialize, Clone, Debug, ToSchema)]
+pub struct GapConstraintDTO {
+    pub min_length: Option<usize>,
+    pub max_length: Option<usize>,
+    #[serde(default)]
+    pub answer_type: AnswerType,
+    #[serde(default = "default_required")]
+    pub required: bool, // optional gaps may be filled with an empty answer
+}
+
+impl Default for GapConstraintDTO {
+    fn default() -> Self {
+        GapConstraintDTO {
+            min_length: None,
+            max_length: None,
+            answer_type: AnswerType::default(),
+            required: true,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, ToSchema)]
+pub struct GameMetadataDTO {
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub language: Option<String>, // language code such as "en" or "de-AT"
+    pub author: Option<String>,
+    pub age_rating: Option<u8>, // minimum recommended age
+}
 
 #[derive(Deserialize, Serialize, Clone, Debug, ToSchema)]
 pub struct GameDTO {
     pub name: String,
+    // text with inline placeholders like `You find {{a noun}}.`, replaces text_section and hints
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(default)]
     pub text_section: Vec<String>,
+    #[serde(default)]
+    pub hints: Vec<String>, // one hint per gap, may be empty
+    #[serde(default)]
+    pub constraints: Vec<GapConstraintDTO>, // one constraint per gap, may be empty
+    #[serde(flatten)]
+    pub metadata: GameMetadataDTO,
 }
 
 #[derive(Deserialize, Serialize, Clone, Debug, ToSchema)]
-pub struct  Override {
+pub struct Override {
     pub force: Option<bool>,
 }
 
-#[derive(Deserialize, Serialize, Clone, Debug)]
-pub struct GameDBDTO {
-    pub id: RecordId,
+// A stored version of a game template, the `game` table holds the latest version of each
+// template and the `game_version` table holds all of them
+#[derive(Deserialize, Serialize, Clone, Debug, ToSchema)]
+pub struct GameVersionDTO {
     pub name: String,
+    #[serde(default)]
+    pub version: u32, // 0 for templates saved before versioning
     pub text_section: Vec<String>,
+    #[serde(default)]
+    pub hints: Vec<String>,
+    #[serde(default)]
+    pub constraints: Vec<GapConstraintDTO>,
+    #[serde(flatten)]
+    pub metadata: GameMetadataDTO,
+    #[serde(default)]
+    pub created_at: u64, // unix timestamp in seconds of the first version
+    #[serde(default)]
+    pub updated_at: u64, // unix timestamp in seconds of this version
+}
+
+// Tells a missing field (None) from an explicit null (Some(None)), which clears the field
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+// Partial update of a game template, fields that are not set are kept and metadata fields set
+// to null are cleared
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct GameUpdateDTO {
+    pub text: Option<String>, // replaces text_section and hints
+    pub text_section: Option<Vec<String>>,
+    pub hints: Option<Vec<String>>,
+    pub constraints: Option<Vec<GapConstraintDTO>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    pub description: Option<Option<String>>,
+    pub tags: Option<Vec<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    pub language: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<String>)]
+    pub author: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    #[schema(value_type = Option<u8>)]
+    pub age_rating: Option<Option<u8>>,
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct GameListQuery {
+    pub tag: Option<String>,
+    pub language: Option<String>,
+    pub sort: Option<String>,   // name (default), created or updated
+    pub order: Option<String>,  // asc (default) or desc
+    pub cursor: Option<String>, // returned with the previous page
+    pub limit: Option<usize>,
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct ImportQuery {
+    pub format: String,        // jsonl, yaml or csv
+    pub dry_run: Option<bool>, // only report what would happen
+    pub update: Option<bool>,  // save existing games as new version instead of skipping them
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct ExportQuery {
+    pub format: String, // jsonl, yaml or csv
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct ImportRowDTO {
+    pub row: usize, // line in jsonl and csv files, entry in yaml files
+    pub name: Option<String>,
+    pub status: String, // created, updated, skipped or error
+    pub errors: Vec<ValidationErrorDTO>,
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct ImportReportDTO {
+    pub success: bool,
+    pub dry_run: bool,
+    pub created: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    pub rows: Vec<ImportRowDTO>,
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct GenerateGameDTO {
+    pub name: Option<String>, // required to save the draft
+    pub text: String,
+    pub mode: String,               // markers, words or every_nth
+    pub marker: Option<String>,     // marker for the markers mode, defaults to "___"
+    pub words: Option<Vec<String>>, // words to blank in the words mode
+    pub every: Option<usize>, // blank every Nth content word in the every_nth mode, defaults to 5
+    pub save: Option<bool>,   // save the draft as game
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct GeneratedGameDTO {
+    pub success: bool,
+    pub saved: bool,
+    pub game: GameDTO,
+    pub blanked: Vec<String>, // the words that were replaced by gaps
+    pub errors: Vec<ValidationErrorDTO>, // problems to fix before the draft can be saved
+}
+
+// Blocked words of a language uploaded by an admin, stored in the `word_list` table
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct WordListDTO {
+    pub language: String, // language code such as "en" or "de"
+    pub words: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct TemplateQuery {
+    pub name: String,
 }
 
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct DiffQuery {
+    pub name: String,
+    pub from: u32,
+    pub to: u32,
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct SectionDiffDTO {
+    pub index: u32,
+    pub from: Option<String>, // None if the section was added
+    pub to: Option<String>,   // None if the section was removed
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct GameDiffDTO {
+    pub name: String,
+    pub from: u32,
+    pub to: u32,
+    pub sections: Vec<SectionDiffDTO>,
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct RestoreDTO {
+    pub name: String,
+    pub version: u32,
+}
+
+// An admin api key, stored in the `api_key` table with the hash of its secret
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ApiKeyDTO {
+    pub key_id: String,
+    pub name: String,
+    pub hash: String, // hex encoded sha256 of the secret
+    pub scopes: Vec<String>,
+    pub created_at: u64,
+    pub expires_at: Option<u64>,
+    #[serde(default)]
+    pub revoked: bool,
+    pub last_used_at: Option<u64>,
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct ApiKeyInfoDTO {
+    pub key_id: String,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: u64,
+    pub expires_at: Option<u64>,
+    pub revoked: bool,
+    pub last_used_at: Option<u64>,
+}
+
+impl From<ApiKeyDTO> for ApiKeyInfoDTO {
+    fn from(key: ApiKeyDTO) -> Self {
+        ApiKeyInfoDTO {
+            key_id: key.key_id,
+            name: key.name,
+            scopes: key.scopes,
+            created_at: key.created_at,
+            expires_at: key.expires_at,
+            revoked: key.revoked,
+            last_used_at: key.last_used_at,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct CreateApiKeyDTO {
+    pub name: String,
+    pub scopes: Vec<String>, // templates:read, templates:write, lobbies:manage or keys:manage
+    pub expires_in: Option<u64>, // seconds until the key expires, never if not set
+}
+
+// A new or rotated api key, the key itself is only returned once
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct ApiKeyCreatedDTO {
+    pub key: String,
+    #[serde(flatten)]
+    pub info: ApiKeyInfoDTO,
+}
+
+// Either the admin password or an api key
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct LoginDTO {
+    pub password: Option<String>,
+    pub api_key: Option<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct RefreshDTO {
+    pub refresh_token: String,
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct TokenResponseDTO {
+    pub access_token: String, // sent as `Authorization: Bearer <token>`
+    pub refresh_token: String,
+    pub token_type: String,
+    pub expires_in: u64, // seconds until the access token expires
+}
 
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct BaseResponse {
@@ -28,11 +300,25 @@ pub struct BaseResponse {
     pub message: Option<String>,
 }
 
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ValidationErrorDTO {
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ValidationResponse {
+    pub success: bool,
+    pub message: Option<String>,
+    pub errors: Vec<ValidationErrorDTO>,
+}
+
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct EndGameResponse {
     pub success: bool,
     pub view: String,
     pub value: Vec<GuessScore>,
+    pub teams: Vec<TeamScore>,
 }
 
 #[derive(Deserialize, Serialize, Debug, ToSchema)]
@@ -40,10 +326,181 @@ pub struct BaseStringDTO {
     pub name: String,
 }
 
+// Criteria for picking a random game template
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct RandomGameDTO {
+    pub tag: Option<String>,
+    pub language: Option<String>,
+    pub min_gaps: Option<usize>,
+    pub max_gaps: Option<usize>,
+    pub not_played_in_last: Option<u32>, // skip templates used in the last N sessions
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ClaimMode {
+    #[default]
+    Unlimited, // users may claim as many gaps as they like
+    OneAtATime, // users have to fill their claimed gap before claiming the next one
+    OnePerUser, // users may only claim a single gap
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationMode {
+    #[default]
+    Off,
+    Mask,   // blocked words are replaced by asterisks
+    Reject, // answers and names with blocked words are rejected
+}
+
+// Settings of a lobby, fields that are not set fall back to their defaults
+#[derive(Deserialize, Serialize, Clone, Debug, ToSchema)]
+#[serde(default)]
+pub struct LobbySettings {
+    pub max_players: Option<usize>,    // unlimited if not set
+    pub answer_length: usize,          // maximum length of answers in characters
+    pub fill_time_limit: Option<u64>,  // seconds until guessing starts even if gaps are left
+    pub guess_time_limit: Option<u64>, // seconds until the ranking is shown even if users are left
+    pub guess_delay: u64,              // seconds between the last filled gap and guessing
+    pub claim_mode: ClaimMode,
+    pub points_per_correct_guess: u32,
+    pub points_per_fooled_user: u32, // points for the author of a gap per wrong guess on it
+    pub moderation: ModerationMode,  // filter for blocked words in answers and player names
+    pub review_answers: bool,        // the host reviews all answers before guessing starts
+    pub offline_grace_period: Option<u64>, // seconds until offline users are removed, never if not set
+}
+
+impl Default for LobbySettings {
+    fn default() -> Self {
+        LobbySettings {
+            max_players: None,
+            answer_length: 140,
+            fill_time_limit: None,
+            guess_time_limit: None,
+            guess_delay: 10,
+            claim_mode: ClaimMode::default(),
+            points_per_correct_guess: 1,
+            points_per_fooled_user: 0,
+            moderation: ModerationMode::default(),
+            review_answers: false,
+            offline_grace_period: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct StartGameDTO {
+    pub name: Option<String>,          // either a template name ...
+    pub random: Option<RandomGameDTO>, // ... or criteria to pick a random template
+    pub version: Option<u32>,          // pins a template version, latest if not set
+    pub teams: Option<Vec<String>>,    // enables teams mode
+    pub settings: Option<LobbySettings>,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ReviewStatus {
+    #[default]
+    Pending,
+    Approved,
+    Hidden,   // the answer is not revealed and can't be guessed
+    Returned, // the answer has to be rewritten by its author
+}
+
+// An answer in the review queue of a lobby
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct ReviewGapDTO {
+    pub gap_id: u32,
+    pub text: String, // text before the gap
+    pub value: String,
+    pub filled: bool,
+    pub filled_by: Option<String>, // name of the author
+    pub status: ReviewStatus,
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct ReviewActionDTO {
+    pub name: String, // lobby id
+    pub gap_id: u32,
+    pub action: String, // approve, hide or return
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct LobbySettingsDTO {
+    pub name: String, // lobby id
+    pub settings: LobbySettings,
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct StartGameResponse {
+    pub name: String, // lobby id
+    pub template: String,
+    pub version: u32,
+}
+
+// A started lobby, stored in the `session` table
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct SessionDTO {
+    pub lobby: String,
+    pub template: String,
+    pub version: u32,
+    pub started_at: u64,
+}
+
+#[derive(Deserialize, Serialize, Debug, ToSchema)]
+pub struct ActiveGameDTO {
+    pub name: String, // lobby id
+    pub template: String,
+    pub version: u32,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct JoinDTO {
+    pub name: String,
+    pub token: String, // user token, chosen by the client and only known to the user
+    pub team: Option<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct UserDTO {
+    pub id: String, // public id of the user, the token is never shared with other users
     pub name: String,
-    pub token: String,
+    pub team: Option<String>,
+    #[serde(default)]
+    pub ready: bool, // set with /ready while the game is waiting for players
+    #[serde(default)]
+    pub online: bool, // connected to the websocket
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct WebsocketMetricsDTO {
+    pub connections: u64,    // currently open connections
+    pub lag_events: u64,     // times a connection fell behind the lobby events
+    pub skipped_events: u64, // events not sent to lagging connections
+    pub resyncs: u64,        // snapshots sent after lagging or resuming
+    pub idle_timeouts: u64,  // connections closed after sending nothing
+    pub channel_capacity: usize,
+    pub ping_interval: u64, // seconds
+    pub idle_timeout: u64,  // seconds
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct KickDTO {
+    pub name: String, // lobby id
+    pub id: String,   // public user id
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct ReadyDTO {
+    pub token: String, // user token
+    pub ready: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct TeamJoinDTO {
+    pub token: String, // user token
+    pub team: String,
 }
 
 #[derive(Deserialize, Serialize, Debug, ToSchema)]
@@ -51,13 +508,19 @@ pub struct PreGapTextDTO {
     pub id: u32,
     pub text: String,
     pub gap_after: bool,
+    pub hint: Option<String>, // what kind of answer is expected for the gap after the text
+    pub constraint: Option<GapConstraintDTO>, // None if there is no gap after the text
 }
 
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct JoinResponse {
     pub success: bool,
+    pub id: String, // public id of the joined user
     pub pre_gaps_text: Vec<PreGapTextDTO>,
     pub current_users: Vec<UserDTO>,
+    pub teams: Option<Vec<String>>,
+    pub settings: LobbySettings,
+    pub seq: u64, // sequence number of the last lobby event, to resume the websocket from
 }
 
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
@@ -65,24 +528,31 @@ pub struct CurrentGapTextDTO {
     pub id: u32,
     pub text: String,
     pub gap_after: bool,
+    pub hint: Option<String>,
+    pub constraint: Option<GapConstraintDTO>,
     pub claimed: bool,
     pub filled: bool,
     pub gap_value: Option<String>,
-    pub filled_by_current_user: bool
+    pub filled_by_current_user: bool,
+    pub team: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct RejoinResponseDTO {
     pub success: bool,
+    pub id: String, // public id of the rejoined user
     pub current_gap_text: Vec<CurrentGapTextDTO>,
     pub view: String,
     pub users: Vec<UserDTO>,
+    pub teams: Option<Vec<String>>,
+    pub settings: LobbySettings,
+    pub seq: u64, // sequence number of the last lobby event, to resume the websocket from
 }
 
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct GapClaimDTO {
     pub gap_id: u32,
-    pub token: String,  // user token
+    pub token: String, // user token
 }
 
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
@@ -108,12 +578,15 @@ pub struct PreGuessingDTO {
     pub success: bool,
     pub gaps: Vec<GapFilledDTO>,
     pub users: Vec<UserDTO>,
+    pub teams: Option<Vec<String>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct GuessDTO {
     pub gap_id: u32,
-    pub token: String, // guessed user token
+    #[serde(default)]
+    pub id: String, // public id of the guessed user
+    pub team: Option<String>, // guessed team in teams mode
 }
 
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
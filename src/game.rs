@@ -0,0 +1,619 @@
+use crate::dto::{
+    BaseResponse, ClaimMode, GuessDTO, ReviewStatus, ValidationErrorDTO, ValidationResponse,
+};
+use crate::moderation::apply_moderation;
+use crate::objects::Lobby;
+use crate::sanitize::sanitize_text;
+use crate::validation::validate_answer;
+use crate::ws_dto::{GapResult, ServerMessage, TeamClaim, UserReady};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::task::AbortHandle;
+use tokio::time::sleep;
+
+type SharedAppState = Arc<RwLock<HashMap<String, RwLock<Lobby>>>>;
+
+// Player actions shared by the REST handlers and the websocket, so both transports behave
+// the same way
+
+// Reason a player action was rejected, sent as REST response or websocket error reply
+pub struct GameError {
+    pub status: StatusCode,
+    pub message: String,
+    pub errors: Vec<ValidationErrorDTO>,
+}
+
+impl GameError {
+    fn new(status: StatusCode, message: &str) -> Self {
+        GameError {
+            status,
+            message: message.to_string(),
+            errors: vec![],
+        }
+    }
+
+    fn not_found(message: &str) -> Self {
+        GameError::new(StatusCode::NOT_FOUND, message)
+    }
+
+    fn bad_request(message: &str) -> Self {
+        GameError::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    fn invalid(message: &str, errors: Vec<ValidationErrorDTO>) -> Self {
+        GameError {
+            status: StatusCode::UNPROCESSABLE_ENTITY,
+            message: message.to_string(),
+            errors,
+        }
+    }
+
+    pub fn into_response(self) -> (StatusCode, Response) {
+        let body = if self.errors.is_empty() {
+            Json(BaseResponse {
+                success: false,
+                message: Some(self.message),
+            })
+            .into_response()
+        } else {
+            Json(ValidationResponse {
+                success: false,
+                message: Some(self.message),
+                errors: self.errors,
+            })
+            .into_response()
+        };
+        (self.status, body)
+    }
+}
+
+// Claims a gap for the user, or for the team of the user in teams mode
+pub fn claim_gap(
+    state: &SharedAppState,
+    game_id: &str,
+    token: &str,
+    gap_id: u32,
+) -> Result<(), GameError> {
+    let read_state = state.read().unwrap();
+    let lobby = read_state
+        .get(game_id)
+        .ok_or_else(|| GameError::not_found("Game not found"))?
+        .read()
+        .unwrap();
+    if lobby.game.view != "fill" {
+        return Err(GameError::bad_request("Game is not in filling mode"));
+    }
+    // in teams mode gaps are claimed for the team of the user
+    let team = lobby.team_of(token);
+    if lobby.teams_mode() && team.is_none() {
+        return Err(GameError::bad_request("Join a team before claiming a gap"));
+    }
+    let gaps = &lobby.game.gaps;
+    let gap = gaps
+        .get(gap_id as usize)
+        .ok_or_else(|| GameError::bad_request("Gap not found"))?;
+    if lobby.settings.claim_mode != ClaimMode::Unlimited {
+        // filled state of all gaps claimed by the user
+        let claimed = gaps
+            .iter()
+            .filter_map(|g| {
+                let g = g.read().unwrap();
+                (g.filled_by.as_deref() == Some(token)).then_some(g.filled)
+            })
+            .collect::<Vec<bool>>();
+        match lobby.settings.claim_mode {
+            ClaimMode::OnePerUser if !claimed.is_empty() => {
+                return Err(GameError::bad_request("Only one gap can be claimed"));
+            }
+            ClaimMode::OneAtATime if claimed.iter().any(|filled| !filled) => {
+                return Err(GameError::bad_request("Fill your claimed gap first"));
+            }
+            _ => {}
+        }
+    }
+    // claiming write lock on the gap
+    let mut gap_to_claim = gap
+        .write()
+        .map_err(|_| GameError::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to claim gap"))?;
+    if gap_to_claim.filled_by.is_some() {
+        return Err(GameError::bad_request("Gap already claimed"));
+    }
+    gap_to_claim.filled_by = Some(token.to_string());
+    gap_to_claim.team = team.clone();
+    // notify all users about the claimed gap
+    lobby
+        .game
+        .connections
+        .send_all(ServerMessage::GapClaimed(gap_id));
+    if let Some(team) = team {
+        lobby
+            .game
+            .connections
+            .send_all(ServerMessage::TeamClaimed(TeamClaim { gap_id, team }));
+    }
+    Ok(())
+}
+
+// Releases a claimed gap that was not filled yet, so another user can claim it
+pub fn unclaim_gap(
+    state: &SharedAppState,
+    game_id: &str,
+    token: &str,
+    gap_id: u32,
+) -> Result<(), GameError> {
+    let read_state = state.read().unwrap();
+    let lobby = read_state
+        .get(game_id)
+        .ok_or_else(|| GameError::not_found("Game not found"))?
+        .read()
+        .unwrap();
+    if lobby.game.view != "fill" {
+        return Err(GameError::bad_request("Game is not in filling mode"));
+    }
+    let mut gap = lobby
+        .game
+        .gaps
+        .get(gap_id as usize)
+        .ok_or_else(|| GameError::bad_request("Gap not found"))?
+        .write()
+        .map_err(|_| GameError::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to unclaim gap"))?;
+    if gap.filled_by.is_none() {
+        return Err(GameError::bad_request("Gap not claimed"));
+    }
+    let claimed_by_user = if lobby.teams_mode() {
+        gap.team.is_some() && gap.team == lobby.team_of(token)
+    } else {
+        gap.filled_by.as_deref() == Some(token)
+    };
+    if !claimed_by_user {
+        return Err(GameError::bad_request("Gap claimed by another user"));
+    }
+    if gap.filled {
+        return Err(GameError::bad_request("Gap already filled"));
+    }
+    gap.filled_by = None;
+    gap.team = None;
+    lobby
+        .game
+        .connections
+        .send_all(ServerMessage::GapUnclaimed(gap_id));
+    Ok(())
+}
+
+// Fills a gap claimed by the user, guessing starts once all gaps are filled
+pub fn fill_gap(
+    state: &SharedAppState,
+    game_id: &str,
+    token: &str,
+    gap_id: u32,
+    content: &str,
+) -> Result<(), GameError> {
+    let read_state = state.read().unwrap();
+    let read_lobby = read_state
+        .get(game_id)
+        .ok_or_else(|| GameError::not_found("Game not found"))?;
+
+    // filling the gap
+    {
+        let lobby = read_lobby.read().unwrap();
+        if lobby.game.view != "fill" && lobby.game.view != "review" {
+            return Err(GameError::bad_request("Game is not in filling mode"));
+        }
+        let mut gap_to_fill = lobby
+            .game
+            .gaps
+            .get(gap_id as usize)
+            .ok_or_else(|| GameError::bad_request("Gap not found"))?
+            .write()
+            .map_err(|_| GameError::new(StatusCode::INTERNAL_SERVER_ERROR, "Failed to fill gap"))?;
+        if gap_to_fill.filled_by.is_none() {
+            return Err(GameError::bad_request("Gap not claimed"));
+        }
+        if lobby.teams_mode() {
+            // any member of the claiming team may fill the gap
+            if gap_to_fill.team.is_none() || gap_to_fill.team != lobby.team_of(token) {
+                return Err(GameError::bad_request("Gap claimed by another team"));
+            }
+        } else if gap_to_fill.filled_by.as_deref() != Some(token) {
+            return Err(GameError::bad_request("Gap claimed by another user"));
+        }
+        // only answers returned by the host may be filled again during the review
+        if lobby.game.view == "review" && gap_to_fill.review != ReviewStatus::Returned {
+            return Err(GameError::bad_request("Answers are being reviewed"));
+        }
+        let content = sanitize_text(content);
+        let errors = validate_answer(
+            &content,
+            &gap_to_fill.constraint,
+            lobby.settings.answer_length,
+        );
+        if !errors.is_empty() {
+            return Err(GameError::invalid("Invalid answer", errors));
+        }
+        let content = apply_moderation(
+            content,
+            &lobby.settings.moderation,
+            lobby.language.as_deref(),
+            "content",
+        )
+        .map_err(|errors| GameError::invalid("Answer contains blocked words", errors))?;
+        gap_to_fill.value = content;
+        gap_to_fill.filled = true;
+        gap_to_fill.review = ReviewStatus::Pending;
+        gap_to_fill.filled_by = Some(token.to_string());
+    }
+    let all_filled = {
+        let lobby = read_lobby.read().unwrap();
+        // notify all users about the filled gap
+        lobby
+            .game
+            .connections
+            .send_all(ServerMessage::GapFilled(gap_id));
+        // check if all gaps are filled
+        lobby.game.gaps.iter().all(|g| {
+            let g_read = g.read().unwrap();
+            !g_read.gap_after || g_read.filled_by.is_some() && g_read.filled
+        })
+    };
+    if all_filled {
+        finish_filling(state, game_id, read_lobby);
+    }
+    Ok(())
+}
+
+// Scores the guesses of the user, guessing ends and the scores are sent once all users have
+// guessed
+pub fn guess(
+    state: &SharedAppState,
+    game_id: &str,
+    token: &str,
+    guesses: &[GuessDTO],
+) -> Result<(), GameError> {
+    let read_state = state.read().unwrap();
+    let read_lobby = read_state
+        .get(game_id)
+        .ok_or_else(|| GameError::not_found("Game not found"))?;
+    let lobby = read_lobby.read().unwrap();
+    if lobby.game.view != "guess" {
+        return Err(GameError::bad_request("Game is not in guessing mode"));
+    }
+    let own_team = lobby.team_of(token);
+    // store number of correct guesses, the authors of gaps guessed wrong and the guessed gaps
+    let mut correct_guesses = 0;
+    let mut fooled = vec![];
+    let mut guessed_gaps = vec![];
+    // process the guesses
+    for guess in guesses {
+        let g = lobby
+            .game
+            .gaps
+            .iter()
+            .find(|g| g.read().unwrap().id == guess.gap_id);
+        if let Some(g) = g {
+            let gap = g.read().unwrap();
+            // hidden answers and the user's own gaps (or the own team's in teams mode) don't count
+            let own_gap = gap.filled_by.as_deref() == Some(token)
+                || lobby.teams_mode() && gap.team.is_some() && gap.team == own_team;
+            if gap.review == ReviewStatus::Hidden || own_gap {
+                continue;
+            }
+            let correct = if lobby.teams_mode() {
+                gap.team.is_some() && gap.team == guess.team
+            } else {
+                gap.filled_by.as_ref().and_then(|t| lobby.id_of(t)).as_ref() == Some(&guess.id)
+            };
+            if gap.gap_after && correct {
+                correct_guesses += 1;
+                guessed_gaps.push((g, true));
+            } else if gap.gap_after && gap.filled {
+                fooled.extend(gap.filled_by.clone());
+                guessed_gaps.push((g, false));
+            }
+        };
+    }
+    // update the user's correct guesses
+    {
+        let mut users = lobby.users.write().unwrap();
+        let user = users
+            .iter_mut()
+            .find(|u| u.token == token)
+            .ok_or_else(|| GameError::not_found("User not found"))?;
+        if user.guessed {
+            return Err(GameError::bad_request("Already guessed"));
+        }
+        user.correct_guesses = correct_guesses;
+        user.guessed = true;
+        for (gap, correct) in guessed_gaps {
+            let mut gap = gap.write().unwrap();
+            if correct {
+                gap.correct_guesses += 1;
+            } else {
+                gap.wrong_guesses += 1;
+            }
+        }
+        for author in &fooled {
+            if let Some(user) = users.iter_mut().find(|u| &u.token == author) {
+                user.fooled += 1;
+            }
+        }
+    }
+
+    let all_guessed = lobby.users.read().unwrap().iter().all(|u| u.guessed);
+    drop(lobby);
+    if all_guessed {
+        finish_guessing(read_lobby);
+    }
+    Ok(())
+}
+
+// Marks the user as ready or not ready while the lobby is waiting for players
+pub fn set_ready(
+    state: &SharedAppState,
+    game_id: &str,
+    token: &str,
+    ready: bool,
+) -> Result<(), GameError> {
+    let read_state = state.read().unwrap();
+    let lobby = read_state
+        .get(game_id)
+        .ok_or_else(|| GameError::not_found("Game not found"))?
+        .read()
+        .unwrap();
+    if lobby.game.view != "waiting" {
+        return Err(GameError::bad_request("Game has already started"));
+    }
+    let id = {
+        let mut users = lobby.users.write().unwrap();
+        let user = users
+            .iter_mut()
+            .find(|u| u.token == token)
+            .ok_or_else(|| GameError::not_found("User not found"))?;
+        user.ready = ready;
+        user.id.clone()
+    };
+    lobby
+        .game
+        .connections
+        .send_all(ServerMessage::UserReady(UserReady { id, ready }));
+    Ok(())
+}
+
+// Ends filling once all gaps are filled or the fill time limit is over, if enabled in the
+// lobby settings the host reviews the answers before guessing starts
+pub fn finish_filling(state: &SharedAppState, game_id: &str, lobby: &RwLock<Lobby>) {
+    {
+        let mut write_lobby = lobby.write().unwrap();
+        if write_lobby.game.view != "fill" {
+            return;
+        }
+        write_lobby.game.set_timer(None);
+        // claims that were not filled in time are released
+        for gap in &write_lobby.game.gaps {
+            let mut gap = gap.write().unwrap();
+            if gap.filled {
+                continue;
+            }
+            if let Some(token) = gap.filled_by.take() {
+                gap.team = None;
+                let connections = &write_lobby.game.connections;
+                connections.send_to(&token, &ServerMessage::ClaimExpired(gap.id));
+                connections.send_all(ServerMessage::GapUnclaimed(gap.id));
+            }
+        }
+        if write_lobby.settings.review_answers && !write_lobby.review_done() {
+            write_lobby.game.view = "review".to_string();
+            write_lobby
+                .game
+                .connections
+                .send_all(ServerMessage::ChangeView("review".to_string()));
+            return;
+        }
+    }
+    start_guessing(state, game_id, lobby);
+}
+
+// Switches the lobby from filling or reviewing to guessing and notifies all users,
+// the guess time limit of the lobby starts after the guess delay
+pub fn start_guessing(state: &SharedAppState, game_id: &str, lobby: &RwLock<Lobby>) {
+    let mut lobby = lobby.write().unwrap();
+    if lobby.game.view != "fill" && lobby.game.view != "review" {
+        return;
+    }
+    lobby.game.view = "guess".to_string();
+    let delay = lobby.settings.guess_delay;
+    lobby
+        .game
+        .connections
+        .send_all(ServerMessage::StartGuessing(delay));
+    let timer = lobby.settings.guess_time_limit.map(|limit| {
+        let state = state.clone();
+        let game_id = game_id.to_string();
+        tokio::spawn(async move {
+            sleep(Duration::from_secs(delay + limit)).await;
+            if let Some(lobby) = state.read().unwrap().get(&game_id) {
+                finish_guessing(lobby);
+            }
+        })
+        .abort_handle()
+    });
+    lobby.game.set_timer(timer);
+}
+
+// Ends filling after the fill time limit even if not all gaps are filled, the returned handle
+// cancels the time limit
+pub fn schedule_fill_time_limit(state: &SharedAppState, game_id: &str, limit: u64) -> AbortHandle {
+    let state = state.clone();
+    let game_id = game_id.to_string();
+    tokio::spawn(async move {
+        sleep(Duration::from_secs(limit)).await;
+        if let Some(lobby) = state.read().unwrap().get(&game_id) {
+            finish_filling(&state, &game_id, lobby);
+        }
+    })
+    .abort_handle()
+}
+
+// Ends guessing once all users have guessed or the guess time limit is over, users that have
+// not guessed yet score nothing, and sends the scores
+fn finish_guessing(lobby: &RwLock<Lobby>) {
+    let mut lobby = lobby.write().unwrap();
+    if lobby.game.view != "guess" {
+        return;
+    }
+    lobby
+        .users
+        .write()
+        .unwrap()
+        .iter_mut()
+        .for_each(|u| u.guessed = true);
+    lobby.game.set_timer(None);
+    lobby.game.view = "ranking".to_string();
+    send_scores(&lobby);
+}
+
+fn send_scores(lobby: &Lobby) {
+    let connections = &lobby.game.connections;
+    connections.send_all(ServerMessage::GuessScores(lobby.guess_scores()));
+    if lobby.teams_mode() {
+        connections.send_all(ServerMessage::TeamScores(lobby.team_scores()));
+    }
+    // tell every author how their answers were guessed
+    let mut results: HashMap<String, Vec<GapResult>> = HashMap::new();
+    for gap in &lobby.game.gaps {
+        let gap = gap.read().unwrap();
+        if let (Some(author), true) = (&gap.filled_by, gap.filled && gap.gap_after) {
+            results.entry(author.clone()).or_default().push(GapResult {
+                gap_id: gap.id,
+                correct_guesses: gap.correct_guesses,
+                wrong_guesses: gap.wrong_guesses,
+            });
+        }
+    }
+    for (author, results) in results {
+        connections.send_to(&author, &ServerMessage::GapResults(results));
+    }
+}
+
+// Marks the user as online after connecting to the websocket
+pub fn user_connected(lobby: &Lobby, token: &str) {
+    let id = {
+        let mut users = lobby.users.write().unwrap();
+        let Some(user) = users.iter_mut().find(|u| u.token == token) else {
+            return;
+        };
+        user.offline_since = None;
+        if user.online {
+            // another connection of the user is open already
+            return;
+        }
+        user.online = true;
+        user.id.clone()
+    };
+    lobby
+        .game
+        .connections
+        .send_all(ServerMessage::UserOnline(id));
+}
+
+// Marks the user as offline once the last websocket connection of the user was closed and
+// removes the user after the grace period of the lobby, unless the user reconnects meanwhile
+pub fn user_disconnected(state: &SharedAppState, game_id: &str, lobby: &Lobby, token: &str) {
+    let offline_since = Instant::now();
+    let id = {
+        // checked under the lock, so a connection opened meanwhile keeps the user online
+        let mut users = lobby.users.write().unwrap();
+        if lobby.game.connections.is_connected(token) {
+            return;
+        }
+        let Some(user) = users.iter_mut().find(|u| u.token == token && u.online) else {
+            return;
+        };
+        user.online = false;
+        user.offline_since = Some(offline_since);
+        user.id.clone()
+    };
+    lobby
+        .game
+        .connections
+        .send_all(ServerMessage::UserOffline(id));
+    if let Some(grace_period) = lobby.settings.offline_grace_period {
+        schedule_removal(state, game_id, token, offline_since, grace_period);
+    }
+}
+
+// Removes the user after the grace period if the user is still offline since the given time
+pub fn schedule_removal(
+    state: &SharedAppState,
+    game_id: &str,
+    token: &str,
+    offline_since: Instant,
+    grace_period: u64,
+) {
+    let state = state.clone();
+    let game_id = game_id.to_string();
+    let token = token.to_string();
+    tokio::spawn(async move {
+        sleep(Duration::from_secs(grace_period)).await;
+        let read_state = state.read().unwrap();
+        let Some(lobby) = read_state.get(&game_id) else {
+            return;
+        };
+        let remove = {
+            let lobby = lobby.read().unwrap();
+            let still_offline = lobby
+                .users
+                .read()
+                .unwrap()
+                .iter()
+                .any(|u| u.token == token && u.offline_since == Some(offline_since));
+            // the ranking is kept complete once the final scores were sent
+            still_offline && !lobby.scores_sent()
+        };
+        if remove {
+            remove_user(lobby, &token);
+        }
+    });
+}
+
+// Removes a user from the lobby, releases the gaps the user claimed but did not fill and
+// notifies the other users, returns false if the user is not in the lobby
+pub fn remove_user(read_lobby: &RwLock<Lobby>, token: &str) -> bool {
+    let lobby = read_lobby.read().unwrap();
+    let removed = {
+        let mut users = lobby.users.write().unwrap();
+        let Some(index) = users.iter().position(|u| u.token == token) else {
+            return false;
+        };
+        users.remove(index)
+    };
+    for gap in &lobby.game.gaps {
+        let mut gap = gap.write().unwrap();
+        if gap.filled_by.as_deref() == Some(token) && !gap.filled {
+            gap.filled_by = None;
+            gap.team = None;
+            lobby
+                .game
+                .connections
+                .send_all(ServerMessage::GapUnclaimed(gap.id));
+        }
+    }
+    lobby
+        .game
+        .connections
+        .send_all_except(token, ServerMessage::UserLeft(removed.id));
+    // the scores were only waiting for the guesses of the removed user
+    let all_guessed = {
+        let users = lobby.users.read().unwrap();
+        !users.is_empty() && users.iter().all(|u| u.guessed)
+    };
+    let guessing = lobby.game.view == "guess";
+    drop(lobby);
+    if !removed.guessed && guessing && all_guessed {
+        finish_guessing(read_lobby);
+    }
+    true
+}
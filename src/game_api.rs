@@ -1,21 +1,29 @@
 use crate::dto::{
-    BaseResponse, CurrentGapTextDTO, EndGameResponse, GapClaimDTO, GapFillDTO, GapFilledDTO,
-    GuessesDTO, JoinResponse, PreGapTextDTO, PreGuessingDTO, RejoinResponseDTO, TokenQuery,
-    UserDTO,
+    BaseResponse, EndGameResponse, GapClaimDTO, GapFillDTO, GapFilledDTO, GuessesDTO, JoinDTO,
+    JoinResponse, PreGapTextDTO, PreGuessingDTO, ReadyDTO, RejoinResponseDTO, ReviewStatus,
+    TeamJoinDTO, TokenQuery, UserDTO, ValidationResponse,
 };
+use crate::game::{claim_gap, fill_gap, guess, schedule_removal, set_ready, unclaim_gap};
+use crate::moderation::apply_moderation;
 use crate::objects::{Lobby, User};
-use crate::ws_dto::{GuessScore, WSMessage};
+use crate::sanitize::sanitize_text;
+use crate::utils::id_generator;
+use crate::validation::validate_player_name;
+use crate::ws_dto::{ServerMessage, TempUser};
 use axum::extract::{Path, Query, State};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use axum::Json;
-use serde_json::to_string;
 use std::collections::HashMap;
+use std::iter;
 use std::sync::{Arc, RwLock};
-use tracing::{event, Level};
+use std::time::Instant;
 
 type SharedAppState = Arc<RwLock<HashMap<String, RwLock<Lobby>>>>;
 
+// Length of the public user ids, unique within a lobby
+const PUBLIC_ID_LENGTH: usize = 8;
+
 // Url: /api/{game_id}/hello
 // Method: GET
 // Response: BaseResponse
@@ -61,15 +69,18 @@ pub async fn hello_handler(
 // Url: /api/game/{game_id}/join
 // User joins the game with the specified id
 // Method: POST
-// Request: UserDTO{name: String, token: String}
+// Request: JoinDTO{name: String, token: String, team: Option<String>}
 // Response: JoinResponse
 #[utoipa::path(
     post,
     path = "/api/{game_id}/join",
-    request_body = UserDTO,
+    request_body = JoinDTO,
     responses(
         (status = 200, description = "User joined successfully", body = JoinResponse),
+        (status = 400, description = "Game can't be joined anymore or is full", body = BaseResponse),
         (status = 404, description = "Game not found", body = BaseResponse),
+        (status = 409, description = "User already joined", body = BaseResponse),
+        (status = 422, description = "Invalid name or name contains blocked words", body = ValidationResponse),
         (status = 500, description = "Internal server error", body = BaseResponse),
     ),
     params(
@@ -82,7 +93,7 @@ pub async fn hello_handler(
 pub async fn join_game_handler(
     State(state): State<SharedAppState>,
     Path(game_id): Path<String>,
-    Json(payload): Json<UserDTO>,
+    Json(payload): Json<JoinDTO>,
 ) -> impl IntoResponse {
     // Add user to the game with the specified id here
     let read_state = state.read().unwrap();
@@ -107,34 +118,110 @@ pub async fn join_game_handler(
             .into_response(),
         );
     }
-    let user = User {
-        name: payload.name.clone(),
-        token: payload.token.clone(),
-        correct_guesses: 0,
-        guessed: false,
-    };
-    let lobby = opt_lobby.unwrap().read().unwrap();
-    lobby.users.write().unwrap().push(user);
-    if lobby.game.tx.receiver_count() > 0 {
-        let send_status = lobby.game.tx.send(
-            to_string(&WSMessage::user_joined(
-                payload.name.clone(),
-                payload.token.clone(),
-            ))
-            .unwrap(),
+    let name = sanitize_text(&payload.name);
+    let errors = validate_player_name(&name);
+    if !errors.is_empty() {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(ValidationResponse {
+                success: false,
+                message: Some("Invalid name".to_string()),
+                errors,
+            })
+            .into_response(),
         );
-        if send_status.is_err() {
-            event!(Level::ERROR, "{}", send_status.unwrap_err());
+    }
+    let lobby = opt_lobby.unwrap().read().unwrap();
+    let name = match apply_moderation(
+        name,
+        &lobby.settings.moderation,
+        lobby.language.as_deref(),
+        "name",
+    ) {
+        Ok(name) => name,
+        Err(errors) => {
             return (
-                StatusCode::INTERNAL_SERVER_ERROR,
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(ValidationResponse {
+                    success: false,
+                    message: Some("Name contains blocked words".to_string()),
+                    errors,
+                })
+                .into_response(),
+            );
+        }
+    };
+    if let Some(team) = &payload.team {
+        if !lobby.has_team(team) {
+            return (
+                StatusCode::BAD_REQUEST,
                 Json(BaseResponse {
                     success: false,
-                    message: Some("Failed to send user joined message".to_string()),
+                    message: Some("Team not found".to_string()),
                 })
                 .into_response(),
             );
         }
     }
+    let joined_at = Instant::now();
+    let id = {
+        // the limit and the token are checked under the same lock as the user is added, so
+        // concurrent joins can't exceed the limit
+        let mut users = lobby.users.write().unwrap();
+        if lobby
+            .settings
+            .max_players
+            .is_some_and(|max| users.len() >= max)
+        {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("Game is full".to_string()),
+                })
+                .into_response(),
+            );
+        }
+        if users.iter().any(|u| u.token == payload.token) {
+            return (
+                StatusCode::CONFLICT,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("User already joined".to_string()),
+                })
+                .into_response(),
+            );
+        }
+        // the public id identifies the user to the others, so the token never has to be shared
+        let id = iter::repeat_with(|| id_generator(PUBLIC_ID_LENGTH))
+            .find(|id| !users.iter().any(|u| &u.id == id))
+            .unwrap();
+        users.push(User {
+            id: id.clone(),
+            name: name.clone(),
+            token: payload.token.clone(),
+            team: payload.team.clone(),
+            correct_guesses: 0,
+            fooled: 0,
+            guessed: false,
+            ready: false,
+            online: false,
+            offline_since: Some(joined_at),
+        });
+        id
+    };
+    // users that never connect to the websocket are removed like users that went offline
+    if let Some(grace_period) = lobby.settings.offline_grace_period {
+        schedule_removal(&state, &game_id, &payload.token, joined_at, grace_period);
+    }
+    lobby
+        .game
+        .connections
+        .send_all(ServerMessage::UserJoined(TempUser {
+            id: id.clone(),
+            name,
+            team: payload.team.clone(),
+        }));
 
     let pre_gap_text = lobby
         .game
@@ -146,6 +233,8 @@ pub async fn join_game_handler(
                 id: g_read.id,
                 text: g_read.text_section.clone(),
                 gap_after: g_read.gap_after,
+                hint: g_read.hint.clone(),
+                constraint: g_read.gap_after.then(|| g_read.constraint.clone()),
             }
         })
         .collect();
@@ -157,8 +246,11 @@ pub async fn join_game_handler(
         .iter()
         .filter(|u| u.token != payload.token)
         .map(|u| UserDTO {
+            id: u.id.clone(),
             name: u.name.clone(),
-            token: u.token.clone(),
+            team: u.team.clone(),
+            ready: u.ready,
+            online: u.online,
         })
         .collect();
 
@@ -166,44 +258,46 @@ pub async fn join_game_handler(
         StatusCode::OK,
         Json(JoinResponse {
             success: true,
+            id,
             pre_gaps_text: pre_gap_text,
             current_users,
+            teams: lobby.teams.clone(),
+            settings: lobby.settings.clone(),
+            seq: lobby.game.connections.seq(),
         })
         .into_response(),
     )
 }
 
-// Url: /api/{game_id}/claim
-// User claims a gap in the game with the specified id
+// Url: /api/{game_id}/team
+// User joins or switches a team in the game with the specified id
 // Method: POST
-// Request: GapClaimDTO{gap_id: u32, name: String}
+// Request: TeamJoinDTO{token: String, team: String}
 // Response: BaseResponse
 #[utoipa::path(
     post,
-    path = "/api/{game_id}/claim",
-    request_body = GapClaimDTO,
+    path = "/api/{game_id}/team",
+    request_body = TeamJoinDTO,
     responses(
-        (status = 200, description = "Gap claimed successfully", body = BaseResponse),
-        (status = 404, description = "Game not found", body = BaseResponse),
-        (status = 400, description = "Gap already claimed", body = BaseResponse),
-        (status = 500, description = "Internal server error", body = BaseResponse),
+        (status = 200, description = "Team joined successfully", body = BaseResponse),
+        (status = 404, description = "Game not found | User not found", body = BaseResponse),
+        (status = 400, description = "Game not in teams mode, not waiting or team not found", body = BaseResponse),
     ),
     params(
         ("game_id" = String, Path, description = "ID of the game")
     ),
-    description = "Claim the gap in the game with the specified id and notifies all users about \
-    the claimed gap via websocket with the message \
-    {\"type\": \"gap_claimed\", \"gap_id\": gap_id}"
+    description = "Join a team in the game with the specified id while it is waiting for players \
+    and notifies all users via websocket with the message \
+    {\"type\": \"team_joined\", \"token\": \"token\", \"team\": \"team\"}"
 )]
-pub async fn claim_gap_handler(
+pub async fn team_join_handler(
     State(state): State<SharedAppState>,
     Path(game_id): Path<String>,
-    Json(payload): Json<GapClaimDTO>,
+    Json(payload): Json<TeamJoinDTO>,
 ) -> impl IntoResponse {
-    // Claim the gap in the game with the specified id here
     let read_state = state.read().unwrap();
-    let read_lobby = read_state.get(&game_id.to_string());
-    if read_lobby.is_none() {
+    let opt_lobby = read_state.get(&game_id.to_string());
+    if opt_lobby.is_none() {
         return (
             StatusCode::NOT_FOUND,
             Json(BaseResponse {
@@ -213,37 +307,66 @@ pub async fn claim_gap_handler(
             .into_response(),
         );
     }
-    let lobby = read_lobby.unwrap().read().unwrap();
-    // claiming write lock on the gaps
-    let gaps = &lobby.game.gaps;
-    let write_gap = gaps.get(payload.gap_id as usize).unwrap().write();
-    if write_gap.is_err() {
+    let lobby = opt_lobby.unwrap().read().unwrap();
+    if !lobby.teams_mode() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Game is not played in teams".to_string()),
+            })
+            .into_response(),
+        );
+    }
+    if lobby.game.view != "waiting" {
         return (
-            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::BAD_REQUEST,
             Json(BaseResponse {
                 success: false,
-                message: Some("Failed to claim gap".to_string()),
+                message: Some("Teams can't be changed anymore".to_string()),
             })
             .into_response(),
         );
     }
-    let mut gap_to_claim = write_gap.unwrap();
-    if gap_to_claim.filled_by.is_some() {
+    if !lobby.has_team(&payload.team) {
         return (
             StatusCode::BAD_REQUEST,
             Json(BaseResponse {
                 success: false,
-                message: Some("Gap already claimed".to_string()),
+                message: Some("Team not found".to_string()),
             })
             .into_response(),
         );
     }
-    gap_to_claim.filled_by = Some(payload.token.clone());
-    // notify all users about the claimed gap
-    let send_status = lobby
+    let (id, name) = {
+        let mut users = lobby.users.write().unwrap();
+        let user = users.iter_mut().find(|u| u.token == payload.token);
+        match user {
+            Some(user) => {
+                user.team = Some(payload.team.clone());
+                (user.id.clone(), user.name.clone())
+            }
+            None => {
+                return (
+                    StatusCode::NOT_FOUND,
+                    Json(BaseResponse {
+                        success: false,
+                        message: Some("User not found".to_string()),
+                    })
+                    .into_response(),
+                );
+            }
+        }
+    };
+    // notify all users about the team change
+    lobby
         .game
-        .tx
-        .send(to_string(&WSMessage::gap_claimed(payload.gap_id)).unwrap());
+        .connections
+        .send_all(ServerMessage::TeamJoined(TempUser {
+            id,
+            name,
+            team: Some(payload.team.clone()),
+        }));
     (
         StatusCode::OK,
         Json(BaseResponse {
@@ -254,6 +377,124 @@ pub async fn claim_gap_handler(
     )
 }
 
+// Url: /api/{game_id}/ready
+// User signals being ready to play while the game is waiting for players
+// Method: POST
+// Request: ReadyDTO{token: String, ready: bool}
+// Response: BaseResponse
+#[utoipa::path(
+    post,
+    path = "/api/{game_id}/ready",
+    request_body = ReadyDTO,
+    responses(
+        (status = 200, description = "Ready state changed", body = BaseResponse),
+        (status = 404, description = "Game not found | User not found", body = BaseResponse),
+        (status = 400, description = "Game has already started", body = BaseResponse),
+    ),
+    params(
+        ("game_id" = String, Path, description = "ID of the game")
+    ),
+    description = "Mark the user as ready or not ready while the game is waiting for players and \
+    notifies all users via websocket with the message \
+    {\"type\": \"user_ready\", \"token\": \"token\", \"ready\": true}"
+)]
+pub async fn ready_handler(
+    State(state): State<SharedAppState>,
+    Path(game_id): Path<String>,
+    Json(payload): Json<ReadyDTO>,
+) -> impl IntoResponse {
+    match set_ready(&state, &game_id, &payload.token, payload.ready) {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(BaseResponse {
+                success: true,
+                message: None,
+            })
+            .into_response(),
+        ),
+        Err(err) => err.into_response(),
+    }
+}
+
+// Url: /api/{game_id}/claim
+// User claims a gap in the game with the specified id
+// Method: POST
+// Request: GapClaimDTO{gap_id: u32, name: String}
+// Response: BaseResponse
+#[utoipa::path(
+    post,
+    path = "/api/{game_id}/claim",
+    request_body = GapClaimDTO,
+    responses(
+        (status = 200, description = "Gap claimed successfully", body = BaseResponse),
+        (status = 404, description = "Game not found", body = BaseResponse),
+        (status = 400, description = "Gap already claimed or claim limit of the lobby reached", body = BaseResponse),
+        (status = 500, description = "Internal server error", body = BaseResponse),
+    ),
+    params(
+        ("game_id" = String, Path, description = "ID of the game")
+    ),
+    description = "Claim the gap in the game with the specified id and notifies all users about \
+    the claimed gap via websocket with the message \
+    {\"type\": \"gap_claimed\", \"gap_id\": gap_id}"
+)]
+pub async fn claim_gap_handler(
+    State(state): State<SharedAppState>,
+    Path(game_id): Path<String>,
+    Json(payload): Json<GapClaimDTO>,
+) -> impl IntoResponse {
+    match claim_gap(&state, &game_id, &payload.token, payload.gap_id) {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(BaseResponse {
+                success: true,
+                message: None,
+            })
+            .into_response(),
+        ),
+        Err(err) => err.into_response(),
+    }
+}
+
+// Url: /api/{game_id}/unclaim
+// User releases a claimed gap that is not filled yet
+// Method: POST
+// Request: GapClaimDTO{gap_id: u32, token: String}
+// Response: BaseResponse
+#[utoipa::path(
+    post,
+    path = "/api/{game_id}/unclaim",
+    request_body = GapClaimDTO,
+    responses(
+        (status = 200, description = "Gap released successfully", body = BaseResponse),
+        (status = 404, description = "Game not found", body = BaseResponse),
+        (status = 400, description = "Gap not claimed, claimed by another user or already filled", body = BaseResponse),
+    ),
+    params(
+        ("game_id" = String, Path, description = "ID of the game")
+    ),
+    description = "Release a claimed gap that is not filled yet, so it can be claimed again, \
+    and notifies all users via websocket with the message \
+    {\"type\": \"gap_unclaimed\", \"gap_id\": gap_id}"
+)]
+pub async fn unclaim_gap_handler(
+    State(state): State<SharedAppState>,
+    Path(game_id): Path<String>,
+    Json(payload): Json<GapClaimDTO>,
+) -> impl IntoResponse {
+    match unclaim_gap(&state, &game_id, &payload.token, payload.gap_id) {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(BaseResponse {
+                success: true,
+                message: None,
+            })
+            .into_response(),
+        ),
+        Err(err) => err.into_response(),
+    }
+}
+
 // Url: /api/{game_id}/fill
 // User fills a gap in the game with the specified id
 // Method: POST
@@ -266,7 +507,8 @@ pub async fn claim_gap_handler(
     responses(
         (status = 200, description = "Gap filled successfully", body = BaseResponse),
         (status = 404, description = "Game not found", body = BaseResponse),
-        (status = 400, description = "Gap not claimed or claimed by another user", body = BaseResponse),
+        (status = 400, description = "Gap not claimed or claimed by another user or team", body = BaseResponse),
+        (status = 422, description = "Answer violates the constraint of the gap or the answer length of the lobby, or contains blocked words", body = ValidationResponse),
         (status = 500, description = "Internal server error", body = BaseResponse),
     ),
     params(
@@ -281,100 +523,23 @@ pub async fn fill_gap_handler(
     Path(game_id): Path<String>,
     Json(payload): Json<GapFillDTO>,
 ) -> impl IntoResponse {
-    // Fill the gap in the game with the specified id here
-    let read_state = state.read().unwrap();
-    let read_lobby = read_state.get(&game_id.to_string());
-    if read_lobby.is_none() {
-        return (
-            StatusCode::NOT_FOUND,
+    match fill_gap(
+        &state,
+        &game_id,
+        &payload.token,
+        payload.gap_id,
+        &payload.content,
+    ) {
+        Ok(()) => (
+            StatusCode::OK,
             Json(BaseResponse {
-                success: false,
-                message: Some("Game not found".to_string()),
+                success: true,
+                message: None,
             })
             .into_response(),
-        );
+        ),
+        Err(err) => err.into_response(),
     }
-
-    // filling the gap
-    {
-        let lobby = read_lobby.unwrap().read().unwrap();
-        let write_gap = lobby
-            .game
-            .gaps
-            .get(payload.gap_id as usize)
-            .unwrap()
-            .write();
-        if write_gap.is_err() {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(BaseResponse {
-                    success: false,
-                    message: Some("Failed to fill gap".to_string()),
-                })
-                .into_response(),
-            );
-        }
-        let mut gap_to_fill = write_gap.unwrap();
-        if gap_to_fill.filled_by.is_none() {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(BaseResponse {
-                    success: false,
-                    message: Some("Gap not claimed".to_string()),
-                })
-                .into_response(),
-            );
-        }
-        if gap_to_fill.filled_by.as_ref().unwrap() != &payload.token {
-            return (
-                StatusCode::BAD_REQUEST,
-                Json(BaseResponse {
-                    success: false,
-                    message: Some("Gap claimed by another user".to_string()),
-                })
-                .into_response(),
-            );
-        }
-        let mut content = payload.content.clone();
-        content.truncate(140);
-        gap_to_fill.value = content;
-    }
-    let mut all_filled = false;
-    {
-        let lobby = read_lobby.unwrap().read().unwrap();
-        // notify all users about the filled gap
-        let send_status = lobby
-            .game
-            .tx
-            .send(to_string(&WSMessage::gap_filled(payload.gap_id)).unwrap());
-        // check if all gaps are filled
-        all_filled = lobby.game.gaps.iter().all(|g| {
-            let g_read = g.read().unwrap();
-            !g_read.gap_after || g_read.filled_by.is_some() && g_read.value.len() > 0
-        });
-    }
-    {
-        if all_filled {
-            {
-                read_lobby.unwrap().write().unwrap().game.view = "guess".to_string();
-            }
-            let send_status = read_lobby
-                .unwrap()
-                .read()
-                .unwrap()
-                .game
-                .tx
-                .send(to_string(&WSMessage::start_guessing(10)).unwrap());
-        }
-    }
-    (
-        StatusCode::OK,
-        Json(BaseResponse {
-            success: true,
-            message: None,
-        })
-        .into_response(),
-    )
 }
 
 // Url: /api/{game_id}/filled
@@ -457,23 +622,26 @@ pub async fn filled_gaps_handler(
         .game
         .gaps
         .iter()
-        .map(|g| {
+        .filter_map(|g| {
             let g_read = g.read().unwrap();
-            if !g_read.gap_after {
-                return None;
+            // optional gaps left empty and hidden answers can't be guessed
+            if !g_read.gap_after || g_read.value.is_empty() || g_read.review == ReviewStatus::Hidden
+            {
+                None
             } else {
-                return Some(GapFilledDTO {
+                Some(GapFilledDTO {
                     gap_id: g_read.id,
                     value: g_read.value.clone(),
-                });
+                })
             }
         })
-        .filter(|g| g.is_some())
-        .map(|g| g.unwrap())
         .collect();
     let users = users.iter().map(|u| UserDTO {
+        id: u.id.clone(),
         name: u.name.clone(),
-        token: u.token.clone(),
+        team: u.team.clone(),
+        ready: u.ready,
+        online: u.online,
     });
     (
         StatusCode::OK,
@@ -481,6 +649,7 @@ pub async fn filled_gaps_handler(
             success: true,
             gaps: filled_gaps,
             users: users.collect(),
+            teams: lobby.teams.clone(),
         })
         .into_response(),
     )
@@ -489,7 +658,7 @@ pub async fn filled_gaps_handler(
 // Url: /api/{game_id}/guess
 // User submits guesses about which gap is filled by which user
 // Method: POST
-// Request: Vec<Guess{gap_id: u32, token: String}>
+// Request: GuessesDTO{token: String, guesses: Vec<GuessDTO{gap_id: u32, id: String}>}
 // Response: BaseResponse
 #[utoipa::path(
     post,
@@ -503,106 +672,37 @@ pub async fn filled_gaps_handler(
     params(
         ("game_id" = String, Path, description = "ID of the game")
     ),
-    description = "Submit guesses about which gap is filled by which user in the game with the \
-    specified id and notifies all users about the guesses via websocket with the message \
-    {\"type\": \"guessed\", \"token\": \"token\"}"
+    description = "Submit guesses about which gap is filled by which user (or which team in teams \
+    mode) in the game with the specified id and notifies all users about the guesses via \
+    websocket with the message {\"type\": \"guessed\", \"token\": \"token\"}"
 )]
 pub async fn guess_gap_handler(
     State(state): State<SharedAppState>,
     Path(game_id): Path<String>,
     Json(payload): Json<GuessesDTO>,
 ) -> impl IntoResponse {
-    // Process the guesses for the game with the specified id here
-    let read_state = state.read().unwrap();
-    let read_lobby = read_state.get(&game_id.to_string());
-    if read_lobby.is_none() {
-        return (
-            StatusCode::NOT_FOUND,
+    match guess(&state, &game_id, &payload.token, &payload.guesses) {
+        Ok(()) => (
+            StatusCode::OK,
             Json(BaseResponse {
-                success: false,
-                message: Some("Game not found".to_string()),
+                success: true,
+                message: None,
             })
             .into_response(),
-        );
-    }
-    {
-        read_lobby.unwrap().write().unwrap().game.view = "ranking".to_string();
-    }
-    let read_lobby = read_lobby.unwrap().read().unwrap();
-    // store number of correct guesses
-    let mut correct_guesses = 0;
-    // process the guesses
-    for guess in &payload.guesses {
-        let g = read_lobby
-            .game
-            .gaps
-            .iter()
-            .find(|g| g.read().unwrap().id == guess.gap_id);
-        if g.is_some() {
-            let gap = g.unwrap().read().unwrap();
-            if gap.gap_after
-                && gap.filled_by.is_some()
-                && gap.filled_by.as_ref().unwrap() == &guess.token
-            {
-                correct_guesses += 1;
-            }
-        };
-    }
-    // update the user's correct guesses
-    {
-        let mut users = read_lobby.users.write().unwrap();
-        let user_index = users.iter().position(|u| u.token == payload.token);
-        if user_index.is_none() {
-            return (
-                StatusCode::NOT_FOUND,
-                Json(BaseResponse {
-                    success: false,
-                    message: Some("User not found".to_string()),
-                })
-                .into_response(),
-            );
-        }
-        users[user_index.unwrap()].correct_guesses = correct_guesses;
-        users[user_index.unwrap()].guessed = true;
+        ),
+        Err(err) => err.into_response(),
     }
-
-    // notify all users about the guesses
-    if read_lobby.users.read().unwrap().iter().all(|u| u.guessed) {
-        let guesses = read_lobby
-            .users
-            .read()
-            .unwrap()
-            .iter()
-            .map(|u| GuessScore {
-                name: u.name.clone(),
-                token: u.token.clone(),
-                score: u.correct_guesses,
-            })
-            .collect();
-        let send_status = read_lobby
-            .game
-            .tx
-            .send(to_string(&WSMessage::guess_scores(guesses)).unwrap());
-    }
-    (
-        StatusCode::OK,
-        Json(BaseResponse {
-            success: true,
-            message: None,
-        })
-        .into_response(),
-    )
 }
 
 // Url: /api/{game_id}/rejoin
 // User rejoins the game with the specified id
 // Method: POST
-// Request: UserDTO{name: String, token: String
-// Response: JoinResponse
+// Request: JoinDTO{name: String, token: String, team: Option<String>}
+// Response: RejoinResponseDTO
 #[utoipa::path(
     post,
     path = "/api/{game_id}/rejoin",
-    request_body = UserDTO,
+    request_body = JoinDTO,
     responses(
         (status = 200, description = "User rejoined successfully", body = RejoinResponseDTO),
         (status = 404, description = "Game not found | User not found", body = BaseResponse),
@@ -616,7 +716,7 @@ pub async fn guess_gap_handler(
 pub async fn rejoin_game_handler(
     State(state): State<SharedAppState>,
     Path(game_id): Path<String>,
-    Json(payload): Json<UserDTO>,
+    Json(payload): Json<JoinDTO>,
 ) -> impl IntoResponse {
     // Rejoin the game with the specified id here
     let read_state = state.read().unwrap();
@@ -639,27 +739,15 @@ pub async fn rejoin_game_handler(
             Json(EndGameResponse {
                 success: true,
                 view: "ranking".to_string(),
-                value: lobby
-                    .users
-                    .read()
-                    .unwrap()
-                    .iter()
-                    .map(|u| GuessScore {
-                        name: u.name.clone(),
-                        token: u.token.clone(),
-                        score: u.correct_guesses,
-                    })
-                    .collect(),
+                value: lobby.guess_scores(),
+                teams: lobby.team_scores(),
             })
             .into_response(),
         );
     }
 
-    let users = &lobby.users.read().unwrap();
-    let user_index = users
-        .iter()
-        .position(|u| u.token == payload.token && u.name == payload.name);
-    if user_index.is_none() {
+    // users are matched by their token only, the stored name may have been masked on join
+    let Some(id) = lobby.id_of(&payload.token) else {
         return (
             StatusCode::NOT_FOUND,
             Json(BaseResponse {
@@ -668,50 +756,19 @@ pub async fn rejoin_game_handler(
             })
             .into_response(),
         );
-    }
-
-    let share_fillings = lobby.game.view != "fill";
-    let pre_gap_text = lobby
-        .game
-        .gaps
-        .iter()
-        .map(|g| {
-            let g_read = g.read().unwrap();
-            let filled_by_current_user = g_read
-                .filled_by
-                .as_ref()
-                .is_some_and(|u| u == &payload.token);
-            let gap_value = if share_fillings {
-                Some(g_read.value.clone())
-            } else {
-                None
-            };
-            CurrentGapTextDTO {
-                id: g_read.id,
-                text: g_read.text_section.clone(),
-                gap_after: g_read.gap_after,
-                claimed: g_read.filled_by.is_some(),
-                filled: !g_read.value.is_empty(),
-                gap_value,
-                filled_by_current_user,
-            }
-        })
-        .collect();
+    };
 
     (
         StatusCode::OK,
         Json(RejoinResponseDTO {
             success: true,
-            current_gap_text: pre_gap_text,
+            id,
+            current_gap_text: lobby.current_gap_text(&payload.token),
             view: lobby.game.view.clone(),
-            users: users
-                .iter()
-                //.filter(|u| u.token != payload.token)
-                .map(|u| UserDTO {
-                    name: u.name.clone(),
-                    token: u.token.clone(),
-                })
-                .collect(),
+            users: lobby.user_dtos(),
+            teams: lobby.teams.clone(),
+            settings: lobby.settings.clone(),
+            seq: lobby.game.connections.seq(),
         })
         .into_response(),
     )
@@ -1,21 +1,33 @@
 mod admin_api;
+mod auth;
+mod bulk;
+mod connections;
 mod db;
 mod dto;
+mod game;
 mod game_api;
+mod moderation;
 mod objects;
+mod rate_limit;
+mod sanitize;
+mod sse;
+mod template;
 mod utils;
+mod validation;
 mod websocket;
 mod ws_dto;
 
 // Our shared state
 use crate::admin_api::auth_check;
 use crate::objects::SecurityAddon;
+use crate::rate_limit::rate_limit;
 use crate::websocket::websocket_handler;
 use axum::http::{Method, StatusCode};
-use axum::routing::post;
+use axum::routing::{delete, patch, post};
 use axum::{middleware, routing::get, Router};
 use objects::Lobby;
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::sync::RwLock;
 use tokio_tungstenite::tungstenite::http::header;
@@ -32,18 +44,48 @@ type SharedAppState = Arc<RwLock<HashMap<String, RwLock<Lobby>>>>;
 #[derive(OpenApi)]
 #[openapi(
     paths(
+        crate::admin_api::login_handler,
+        crate::admin_api::refresh_handler,
         crate::admin_api::new_game_handler,
         crate::admin_api::available_games_handler,
+        crate::admin_api::get_game_handler,
+        crate::admin_api::update_game_handler,
+        crate::admin_api::rename_game_handler,
+        crate::admin_api::delete_game_handler,
+        crate::admin_api::game_versions_handler,
+        crate::admin_api::game_diff_handler,
+        crate::admin_api::restore_game_handler,
+        crate::admin_api::import_games_handler,
+        crate::admin_api::export_games_handler,
+        crate::admin_api::generate_game_handler,
         crate::admin_api::start_game_handler,
         crate::admin_api::active_games_handler,
         crate::admin_api::close_game_handler,
+        crate::admin_api::kick_user_handler,
+        crate::admin_api::websocket_metrics_handler,
         crate::admin_api::start_fill_handler,
+        crate::admin_api::lobby_settings_handler,
+        crate::admin_api::review_queue_handler,
+        crate::admin_api::review_answer_handler,
+        crate::admin_api::word_lists_handler,
+        crate::admin_api::upload_word_list_handler,
+        crate::admin_api::delete_word_list_handler,
+        crate::admin_api::api_keys_handler,
+        crate::admin_api::create_api_key_handler,
+        crate::admin_api::rotate_api_key_handler,
+        crate::admin_api::revoke_api_key_handler,
+        crate::admin_api::revoke_tokens_handler,
+        crate::websocket::schema_handler,
         crate::game_api::hello_handler,
         crate::game_api::join_game_handler,
+        crate::game_api::team_join_handler,
+        crate::game_api::ready_handler,
         crate::game_api::claim_gap_handler,
+        crate::game_api::unclaim_gap_handler,
         crate::game_api::fill_gap_handler,
         crate::game_api::filled_gaps_handler,
         crate::game_api::guess_gap_handler,
+        crate::sse::events_handler,
     ),
     modifiers(&SecurityAddon)
 )]
@@ -69,14 +111,80 @@ async fn main() {
     // Create a shared state for the application that can hold multiple GamesStates.
     let app_state: SharedAppState = Arc::new(RwLock::new(HashMap::new()));
 
-    let admin_routes = Router::new()
-        .route("/new", post(admin_api::new_game_handler))
+    // admin routes, grouped by the scope an api key needs for them
+    let template_read_routes = Router::new()
         .route("/available", get(admin_api::available_games_handler))
+        .route("/game/:name", get(admin_api::get_game_handler))
+        .route("/versions", get(admin_api::game_versions_handler))
+        .route("/diff", get(admin_api::game_diff_handler))
+        .route("/export", get(admin_api::export_games_handler))
+        .route("/wordlists", get(admin_api::word_lists_handler))
+        .layer(middleware::from_fn_with_state(
+            auth::TEMPLATES_READ,
+            auth_check,
+        ));
+    let template_write_routes = Router::new()
+        .route("/new", post(admin_api::new_game_handler))
+        .route(
+            "/game/:name",
+            patch(admin_api::update_game_handler).delete(admin_api::delete_game_handler),
+        )
+        .route("/game/:name/rename", post(admin_api::rename_game_handler))
+        .route("/restore", post(admin_api::restore_game_handler))
+        .route("/import", post(admin_api::import_games_handler))
+        .route("/generate", post(admin_api::generate_game_handler))
+        .route("/wordlists", post(admin_api::upload_word_list_handler))
+        .route(
+            "/wordlists/:language",
+            delete(admin_api::delete_word_list_handler),
+        )
+        .layer(middleware::from_fn_with_state(
+            auth::TEMPLATES_WRITE,
+            auth_check,
+        ));
+    let lobby_routes = Router::new()
         .route("/start", post(admin_api::start_game_handler))
         .route("/active", get(admin_api::active_games_handler))
         .route("/close", post(admin_api::close_game_handler))
+        .route("/kick", post(admin_api::kick_user_handler))
+        .route("/metrics", get(admin_api::websocket_metrics_handler))
         .route("/startfill", post(admin_api::start_fill_handler))
-        .layer(middleware::from_fn(auth_check))
+        .route("/settings", post(admin_api::lobby_settings_handler))
+        .route(
+            "/review",
+            get(admin_api::review_queue_handler).post(admin_api::review_answer_handler),
+        )
+        .layer(middleware::from_fn_with_state(
+            auth::LOBBIES_MANAGE,
+            auth_check,
+        ));
+    let key_routes = Router::new()
+        .route(
+            "/keys",
+            get(admin_api::api_keys_handler).post(admin_api::create_api_key_handler),
+        )
+        .route(
+            "/keys/:key_id/rotate",
+            post(admin_api::rotate_api_key_handler),
+        )
+        .route(
+            "/keys/:key_id/revoke",
+            post(admin_api::revoke_api_key_handler),
+        )
+        .route("/tokens/revoke", post(admin_api::revoke_tokens_handler))
+        .layer(middleware::from_fn_with_state(
+            auth::KEYS_MANAGE,
+            auth_check,
+        ));
+    let login_routes = Router::new()
+        .route("/login", post(admin_api::login_handler))
+        .route("/refresh", post(admin_api::refresh_handler));
+    let admin_routes = Router::new()
+        .merge(login_routes)
+        .merge(template_read_routes)
+        .merge(template_write_routes)
+        .merge(lobby_routes)
+        .merge(key_routes)
         .with_state(app_state.clone());
 
     // game routes
@@ -84,15 +192,27 @@ async fn main() {
         .route("/hello", get(game_api::hello_handler))
         .route("/join", post(game_api::join_game_handler))
         .route("/rejoin", post(game_api::rejoin_game_handler))
+        .route("/team", post(game_api::team_join_handler))
+        .route("/ready", post(game_api::ready_handler))
         .route("/claim", post(game_api::claim_gap_handler))
+        .route("/unclaim", post(game_api::unclaim_gap_handler))
         .route("/fill", post(game_api::fill_gap_handler))
         .route("/filled", get(game_api::filled_gaps_handler))
         .route("/guess", post(game_api::guess_gap_handler))
+        .route("/events", get(sse::events_handler))
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            rate_limit,
+        ))
         .with_state(app_state.clone());
 
     // websocket routes
     let websocket_routes = Router::new()
         .route("/com", get(websocket_handler))
+        .route_layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            rate_limit,
+        ))
         .with_state(app_state.clone());
 
     // Swagger UI
@@ -101,8 +221,21 @@ async fn main() {
     // CORS
     let cors_layer = CorsLayer::new()
         .allow_origin(Any)
-        .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
-        .allow_headers([header::CONTENT_TYPE, header::ACCEPT]);
+        .allow_methods([
+            Method::GET,
+            Method::POST,
+            Method::PATCH,
+            Method::DELETE,
+            Method::OPTIONS,
+        ])
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::ACCEPT,
+            header::AUTHORIZATION,
+            header::HeaderName::from_static("apikey"),
+            // sent by browsers when they reconnect to an event stream
+            header::HeaderName::from_static("last-event-id"),
+        ]);
 
     // Serve static files and main routing
     let serve_dir = ServeDir::new("assets").not_found_service(ServeFile::new("assets/index.html"));
@@ -116,6 +249,7 @@ async fn main() {
         )
         .nest("/api/admin", admin_routes)
         .nest("/api/:game_id", game_routes)
+        .route("/websocket/schema", get(websocket::schema_handler))
         .nest("/websocket/:game_id", websocket_routes)
         .merge(swagger_ui)
         .nest_service(
@@ -132,5 +266,11 @@ async fn main() {
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:3000").await.unwrap();
     tracing::debug!("listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, app).await.unwrap();
+    // the client address is needed for rate limiting
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
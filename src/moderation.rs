@@ -0,0 +1,228 @@
+use crate::dto::{ModerationMode, ValidationErrorDTO, WordListDTO};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::RwLock;
+use surrealdb::engine::remote::ws::Client;
+use surrealdb::Surreal;
+use unicode_segmentation::UnicodeSegmentation;
+
+// Blocked words shipped with the server, admins can add more per language
+const BUILTIN_WORDS: [(&str, &[&str]); 2] = [
+    (
+        "en",
+        &[
+            "arse",
+            "arsehole",
+            "ass",
+            "asshole",
+            "bastard",
+            "bitch",
+            "bollocks",
+            "bullshit",
+            "crap",
+            "cunt",
+            "dickhead",
+            "fag",
+            "faggot",
+            "fuck",
+            "fucker",
+            "fucking",
+            "motherfucker",
+            "nigga",
+            "nigger",
+            "piss",
+            "prick",
+            "pussy",
+            "retard",
+            "shit",
+            "shitty",
+            "slut",
+            "twat",
+            "wanker",
+            "whore",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "arsch",
+            "arschloch",
+            "bastard",
+            "fick",
+            "ficken",
+            "fotze",
+            "hure",
+            "hurensohn",
+            "kacke",
+            "missgeburt",
+            "nutte",
+            "scheisse",
+            "scheiße",
+            "schlampe",
+            "schwuchtel",
+            "spast",
+            "wichser",
+        ],
+    ),
+];
+
+lazy_static! {
+    // Word lists uploaded by admins per language, loaded from the `word_list` table
+    static ref WORD_LISTS: RwLock<Option<HashMap<String, Vec<String>>>> = RwLock::new(None);
+}
+
+// Loads the uploaded word lists from the db unless they are cached already
+pub async fn load_word_lists(con: &Surreal<Client>) -> surrealdb::Result<()> {
+    if WORD_LISTS.read().unwrap().is_some() {
+        return Ok(());
+    }
+    let lists: Vec<WordListDTO> = con.select("word_list").await?;
+    *WORD_LISTS.write().unwrap() = Some(lists.into_iter().map(|l| (l.language, l.words)).collect());
+    Ok(())
+}
+
+// Replaces the cached word list of a language after it was changed in the db, if the lists
+// were not loaded yet they are all read from the db on the next use
+pub fn cache_word_list(language: &str, words: Option<Vec<String>>) {
+    if let Some(lists) = WORD_LISTS.write().unwrap().as_mut() {
+        match words {
+            Some(words) => lists.insert(language.to_string(), words),
+            None => lists.remove(language),
+        };
+    }
+}
+
+// Lowercases a word and undoes common leetspeak substitutions like "sh1t" or "@ss"
+fn normalize_word(word: &str) -> String {
+    word.to_lowercase()
+        .chars()
+        .map(|c| match c {
+            '0' => 'o',
+            '1' | '!' | '|' => 'i',
+            '3' => 'e',
+            '4' | '@' => 'a',
+            '5' | '$' => 's',
+            '7' | '+' => 't',
+            '8' => 'b',
+            '9' => 'g',
+            c => c,
+        })
+        .collect()
+}
+
+// Collapses repeated letters, so stretched words like "fuuuck" can be matched
+fn collapse_repeats(word: &str) -> String {
+    let mut chars: Vec<char> = word.chars().collect();
+    chars.dedup();
+    chars.into_iter().collect()
+}
+
+// Blocked words for a language code like "de-AT", the lists of "de-at" and "de" are used, all
+// languages if it is not known
+fn blocked_words(language: Option<&str>) -> Vec<String> {
+    let language = language.map(|l| l.to_lowercase());
+    let primary = language
+        .as_ref()
+        .map(|l| l.split('-').next().unwrap_or(l).to_string());
+    let matches = |l: &str| {
+        let l = l.to_lowercase();
+        language.is_none() || language.as_ref() == Some(&l) || primary.as_ref() == Some(&l)
+    };
+    let mut words: Vec<String> = BUILTIN_WORDS
+        .iter()
+        .filter(|(l, _)| matches(l))
+        .flat_map(|(_, words)| words.iter().map(|w| w.to_string()))
+        .collect();
+    if let Some(lists) = WORD_LISTS.read().unwrap().as_ref() {
+        lists
+            .iter()
+            .filter(|(l, _)| matches(l))
+            .for_each(|(_, list)| words.extend(list.iter().map(|w| normalize_word(w))));
+    }
+    words
+}
+
+// Symbols that are used as letters in leetspeak but may also be punctuation around a word
+const LEET_SYMBOLS: &str = "!|@$+";
+
+fn is_blocked(word: &str, blocked: &[String]) -> bool {
+    let is_symbol = |c: char| LEET_SYMBOLS.contains(c);
+    [
+        word,
+        word.trim_end_matches(is_symbol),
+        word.trim_start_matches(is_symbol),
+        word.trim_matches(is_symbol),
+    ]
+    .iter()
+    .any(|w| {
+        let normalized = normalize_word(w);
+        let collapsed = collapse_repeats(&normalized);
+        // only stretched words are compared collapsed, so "as" does not match "ass"
+        blocked.iter().any(|b| {
+            *b == normalized
+                || normalized.chars().count() > b.chars().count()
+                    && collapse_repeats(b) == collapsed
+        })
+    })
+}
+
+// Byte ranges of the words in a text, leetspeak symbols count as part of a word
+fn word_spans(text: &str) -> Vec<(usize, usize)> {
+    let is_word_char = |c: char| c.is_alphanumeric() || LEET_SYMBOLS.contains(c);
+    let mut spans = vec![];
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (is_word_char(c), start) {
+            (true, None) => start = Some(i),
+            (false, Some(s)) => {
+                spans.push((s, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, text.len()));
+    }
+    spans
+}
+
+// Checks a text for blocked words of the language and returns it with those words masked,
+// None if the text is clean
+pub fn moderate(text: &str, language: Option<&str>) -> Option<String> {
+    let blocked = blocked_words(language);
+    let mut masked = String::new();
+    let mut last = 0;
+    let mut found = false;
+    for (start, end) in word_spans(text) {
+        let word = &text[start..end];
+        if is_blocked(word, &blocked) {
+            found = true;
+            masked.push_str(&text[last..start]);
+            masked.push_str(&"*".repeat(word.graphemes(true).count()));
+            last = end;
+        }
+    }
+    masked.push_str(&text[last..]);
+    found.then_some(masked)
+}
+
+// Applies the moderation mode of a lobby to an answer or player name
+pub fn apply_moderation(
+    text: String,
+    mode: &ModerationMode,
+    language: Option<&str>,
+    field: &str,
+) -> Result<String, Vec<ValidationErrorDTO>> {
+    if *mode == ModerationMode::Off {
+        return Ok(text);
+    }
+    match (mode, moderate(&text, language)) {
+        (ModerationMode::Reject, Some(_)) => Err(vec![ValidationErrorDTO {
+            field: field.to_string(),
+            message: "Contains blocked words".to_string(),
+        }]),
+        (_, Some(masked)) => Ok(masked),
+        (_, None) => Ok(text),
+    }
+}
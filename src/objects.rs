@@ -1,43 +1,86 @@
+use crate::connections::Connections;
+use crate::dto::{
+    CurrentGapTextDTO, GameVersionDTO, GapConstraintDTO, LobbySettings, ReviewStatus, UserDTO,
+};
+use crate::ws_dto::{GuessScore, Snapshot, TeamScore};
 use std::sync::RwLock;
-use tokio::sync::broadcast;
+use std::time::Instant;
+use tokio::task::AbortHandle;
 use utoipa::{
-    openapi::security::{ApiKey, ApiKeyValue, SecurityScheme},
+    openapi::security::{ApiKey, ApiKeyValue, Http, HttpAuthScheme, SecurityScheme},
     Modify,
 };
 
 pub struct User {
+    pub id: String, // public id shown to the other users, the token stays secret
     pub name: String,
     pub token: String,
+    pub team: Option<String>,
     pub correct_guesses: u32,
+    pub fooled: u32, // wrong guesses on gaps filled by the user
     pub guessed: bool,
+    pub ready: bool,
+    pub online: bool, // connected to the websocket
+    pub offline_since: Option<Instant>,
 }
 
 pub struct Gap {
     pub id: u32,
     pub text_section: String,
     pub gap_after: bool,
+    pub hint: Option<String>,
+    pub constraint: GapConstraintDTO,
     pub value: String,
+    pub filled: bool,              // optional gaps may be filled with an empty value
     pub filled_by: Option<String>, // user token
+    pub team: Option<String>,      // claiming team in teams mode
+    pub review: ReviewStatus,      // review by the host if enabled in the lobby settings
+    pub correct_guesses: u32,      // users that guessed the author of the gap
+    pub wrong_guesses: u32,
 }
 
 pub struct GameState {
-    // Channel used to send messages to all connected clients.
-    pub tx: broadcast::Sender<String>,
+    // Websocket connections of the users, used to send them messages
+    pub connections: Connections,
     pub gaps: Vec<RwLock<Gap>>,
     pub view: String,
+    // Time limit of the current phase, aborted when the phase ends early or the lobby is removed
+    pub timer: Option<AbortHandle>,
+}
+
+impl GameState {
+    // Replaces the time limit of the current phase, the previous one does not fire anymore
+    pub fn set_timer(&mut self, timer: Option<AbortHandle>) {
+        if let Some(previous) = std::mem::replace(&mut self.timer, timer) {
+            previous.abort();
+        }
+    }
+}
+
+impl Drop for GameState {
+    fn drop(&mut self) {
+        self.set_timer(None);
+    }
 }
 
 pub struct Lobby {
+    // Name and version of the template the lobby was started with
+    pub template: String,
+    pub version: u32,
+    pub language: Option<String>, // language of the template, used for moderation
     pub users: RwLock<Vec<User>>,
+    // Team names if the lobby is played in teams mode
+    pub teams: Option<Vec<String>>,
+    pub settings: LobbySettings,
     pub game: GameState,
-    pub finished: bool,
 }
 
 impl Lobby {
-    pub fn new(gaps: Vec<String>) -> Self {
+    pub fn new(game: GameVersionDTO, teams: Option<Vec<String>>, settings: LobbySettings) -> Self {
         // Create a new game state with the specified gaps
         // The last gap should not have a gap after it
-        let gaps: Vec<RwLock<Gap>> = gaps
+        let gaps: Vec<RwLock<Gap>> = game
+            .text_section
             .iter()
             .enumerate()
             .map(|g| {
@@ -45,24 +88,194 @@ impl Lobby {
                     id: g.0 as u32,
                     text_section: g.1.clone(),
                     gap_after: true,
+                    hint: game.hints.get(g.0).filter(|h| !h.is_empty()).cloned(),
+                    constraint: game.constraints.get(g.0).cloned().unwrap_or_default(),
                     value: "".to_string(),
+                    filled: false,
                     filled_by: None,
+                    team: None,
+                    review: ReviewStatus::default(),
+                    correct_guesses: 0,
+                    wrong_guesses: 0,
                 })
             })
             .collect();
-        gaps.last().unwrap().write().unwrap().gap_after = false;
+        if let Some(last) = gaps.last() {
+            last.write().unwrap().gap_after = false;
+        }
         let game_state = GameState {
-            tx: broadcast::channel(100).0,
+            connections: Connections::default(),
             gaps,
             view: "waiting".to_string(),
+            timer: None,
         };
         // Create a new lobby with the specified id and game state
         Lobby {
+            template: game.name,
+            version: game.version,
+            language: game.metadata.language,
             users: RwLock::new(vec![]),
+            teams,
+            settings,
             game: game_state,
-            finished: false,
         }
     }
+
+    pub fn teams_mode(&self) -> bool {
+        self.teams.is_some()
+    }
+
+    pub fn has_team(&self, team: &str) -> bool {
+        self.teams
+            .as_ref()
+            .is_some_and(|teams| teams.iter().any(|t| t == team))
+    }
+
+    // Team of the user with the specified token, if any
+    pub fn team_of(&self, token: &str) -> Option<String> {
+        self.users
+            .read()
+            .unwrap()
+            .iter()
+            .find(|u| u.token == token)
+            .and_then(|u| u.team.clone())
+    }
+
+    // Public id of the user with the specified token
+    pub fn id_of(&self, token: &str) -> Option<String> {
+        self.users
+            .read()
+            .unwrap()
+            .iter()
+            .find(|u| u.token == token)
+            .map(|u| u.id.clone())
+    }
+
+    // Token of the user with the specified public id
+    pub fn token_of(&self, id: &str) -> Option<String> {
+        self.users
+            .read()
+            .unwrap()
+            .iter()
+            .find(|u| u.id == id)
+            .map(|u| u.token.clone())
+    }
+
+    // Whether the host has approved or hidden all filled and returned answers
+    pub fn review_done(&self) -> bool {
+        self.game.gaps.iter().all(|g| {
+            let g = g.read().unwrap();
+            !g.gap_after
+                || !g.filled && g.review != ReviewStatus::Returned
+                || g.review == ReviewStatus::Approved
+                || g.review == ReviewStatus::Hidden
+        })
+    }
+
+    // Whether the final scores were sent, which happens when guessing ends
+    pub fn scores_sent(&self) -> bool {
+        self.game.view == "ranking"
+    }
+
+    // Score of a user weighted by the lobby settings
+    pub fn score_of(&self, user: &User) -> u32 {
+        user.correct_guesses * self.settings.points_per_correct_guess
+            + user.fooled * self.settings.points_per_fooled_user
+    }
+
+    pub fn guess_scores(&self) -> Vec<GuessScore> {
+        self.users
+            .read()
+            .unwrap()
+            .iter()
+            .map(|u| GuessScore {
+                id: u.id.clone(),
+                name: u.name.clone(),
+                score: self.score_of(u),
+            })
+            .collect()
+    }
+
+    pub fn user_dtos(&self) -> Vec<UserDTO> {
+        self.users
+            .read()
+            .unwrap()
+            .iter()
+            .map(|u| UserDTO {
+                id: u.id.clone(),
+                name: u.name.clone(),
+                team: u.team.clone(),
+                ready: u.ready,
+                online: u.online,
+            })
+            .collect()
+    }
+
+    // Gaps as seen by the user with the token, answers stay private while filling and while
+    // the host reviews them
+    pub fn current_gap_text(&self, token: &str) -> Vec<CurrentGapTextDTO> {
+        let share_fillings = self.game.view != "fill" && self.game.view != "review";
+        self.game
+            .gaps
+            .iter()
+            .map(|g| {
+                let g_read = g.read().unwrap();
+                let filled_by_current_user = g_read.filled_by.as_ref().is_some_and(|u| u == token);
+                let gap_value = if share_fillings && g_read.review != ReviewStatus::Hidden {
+                    Some(g_read.value.clone())
+                } else {
+                    None
+                };
+                CurrentGapTextDTO {
+                    id: g_read.id,
+                    text: g_read.text_section.clone(),
+                    gap_after: g_read.gap_after,
+                    hint: g_read.hint.clone(),
+                    constraint: g_read.gap_after.then(|| g_read.constraint.clone()),
+                    claimed: g_read.filled_by.is_some(),
+                    filled: g_read.filled,
+                    gap_value,
+                    filled_by_current_user,
+                    team: g_read.team.clone(),
+                }
+            })
+            .collect()
+    }
+
+    // Full state of the lobby for the user with the token, sent to clients that missed events
+    pub fn snapshot(&self, token: &str) -> Snapshot {
+        let ranking = self.game.view == "ranking";
+        Snapshot {
+            seq: self.game.connections.seq(),
+            view: self.game.view.clone(),
+            current_gap_text: self.current_gap_text(token),
+            users: self.user_dtos(),
+            teams: self.teams.clone(),
+            settings: self.settings.clone(),
+            scores: if ranking { self.guess_scores() } else { vec![] },
+            team_scores: if ranking { self.team_scores() } else { vec![] },
+        }
+    }
+
+    // Aggregates the scores of all team members per team
+    pub fn team_scores(&self) -> Vec<TeamScore> {
+        let users = self.users.read().unwrap();
+        self.teams
+            .iter()
+            .flatten()
+            .map(|team| {
+                let members = users
+                    .iter()
+                    .filter(|u| u.team.as_ref() == Some(team))
+                    .collect::<Vec<&User>>();
+                TeamScore {
+                    team: team.clone(),
+                    members: members.iter().map(|u| u.name.clone()).collect(),
+                    score: members.iter().map(|u| self.score_of(u)).sum(),
+                }
+            })
+            .collect()
+    }
 }
 
 pub struct SecurityAddon;
@@ -73,6 +286,11 @@ impl Modify for SecurityAddon {
         components.add_security_scheme(
             "ApiKey",
             SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("apikey"))),
+        );
+        // tokens issued by /api/admin/login
+        components.add_security_scheme(
+            "Bearer",
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer)),
         )
     }
 }
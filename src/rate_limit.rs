@@ -0,0 +1,245 @@
+use crate::dto::{BaseResponse, TokenQuery};
+use crate::objects::Lobby;
+use axum::body::{to_bytes, Body};
+use axum::extract::{ConnectInfo, Path, Query, Request, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+type SharedAppState = Arc<RwLock<HashMap<String, RwLock<Lobby>>>>;
+
+// Requests with larger bodies are rejected before they are buffered to read the token
+const MAX_BODY_SIZE: usize = 64 * 1024;
+// Buckets are pruned once this many keys are tracked
+const MAX_TRACKED_KEYS: usize = 10_000;
+
+fn env_or<T: std::str::FromStr>(name: &str, default: T) -> T {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+lazy_static! {
+    static ref IP_LIMITER: RateLimiter = RateLimiter::new(
+        env_or("RATE_LIMIT_IP_BURST", 60.0),
+        env_or("RATE_LIMIT_IP_PER_MINUTE", 120.0),
+    );
+    static ref TOKEN_LIMITER: RateLimiter = RateLimiter::new(
+        env_or("RATE_LIMIT_TOKEN_BURST", 20.0),
+        env_or("RATE_LIMIT_TOKEN_PER_MINUTE", 60.0),
+    );
+    static ref PROBE_GUARD: ProbeGuard = ProbeGuard::new(
+        env_or("PROBE_LIMIT", 20),
+        Duration::from_secs(env_or("PROBE_WINDOW_SECONDS", 600)),
+        Duration::from_secs(env_or("PROBE_BLOCK_SECONDS", 900)),
+    );
+    // Use the client ip of the X-Forwarded-For header when running behind a reverse proxy
+    static ref TRUST_PROXY: bool = env_or("TRUST_PROXY", false);
+    // Number of trusted proxies in front of the server, each appends the ip it got the request
+    // from to X-Forwarded-For, entries left of theirs are sent by the client and can be forged
+    static ref TRUSTED_PROXY_HOPS: usize = env_or("TRUSTED_PROXY_HOPS", 1);
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+// Token bucket per key, each request takes one token and tokens refill continuously
+struct RateLimiter {
+    burst: f64,
+    per_second: f64,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    fn new(burst: f64, per_minute: f64) -> Self {
+        RateLimiter {
+            burst,
+            per_second: per_minute / 60.0,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Takes a token from the bucket of the key, returns the time to wait if it is empty
+    fn check(&self, key: &str) -> Result<(), Duration> {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > MAX_TRACKED_KEYS {
+            // buckets that are full again can be dropped without changing behavior
+            let refill = Duration::from_secs_f64(self.burst / self.per_second);
+            buckets.retain(|_, b| now.duration_since(b.updated) < refill);
+        }
+        let bucket = buckets.entry(key.to_string()).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.per_second).min(self.burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / self.per_second,
+            ))
+        }
+    }
+}
+
+// Blocks ips that look up too many game ids that don't exist within a time window
+struct ProbeGuard {
+    limit: u32,
+    window: Duration,
+    block: Duration,
+    probes: Mutex<HashMap<IpAddr, (u32, Instant)>>,
+    blocked: Mutex<HashMap<IpAddr, Instant>>,
+}
+
+impl ProbeGuard {
+    fn new(limit: u32, window: Duration, block: Duration) -> Self {
+        ProbeGuard {
+            limit,
+            window,
+            block,
+            probes: Mutex::new(HashMap::new()),
+            blocked: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Time until the ip is unblocked, if it is blocked
+    fn blocked_for(&self, ip: &IpAddr) -> Option<Duration> {
+        let mut blocked = self.blocked.lock().unwrap();
+        let until = *blocked.get(ip)?;
+        let now = Instant::now();
+        if until <= now {
+            blocked.remove(ip);
+            return None;
+        }
+        Some(until - now)
+    }
+
+    // Counts a lookup of an unknown game id and blocks the ip once it exceeds the limit
+    fn record(&self, ip: IpAddr) {
+        let now = Instant::now();
+        let mut probes = self.probes.lock().unwrap();
+        if probes.len() > MAX_TRACKED_KEYS {
+            probes.retain(|_, (_, start)| now.duration_since(*start) < self.window);
+        }
+        let (count, start) = probes.entry(ip).or_insert((0, now));
+        if now.duration_since(*start) >= self.window {
+            *count = 0;
+            *start = now;
+        }
+        *count += 1;
+        if *count > self.limit {
+            probes.remove(&ip);
+            let mut blocked = self.blocked.lock().unwrap();
+            if blocked.len() > MAX_TRACKED_KEYS {
+                blocked.retain(|_, until| *until > now);
+            }
+            blocked.insert(ip, now + self.block);
+        }
+    }
+}
+
+fn too_many_requests(retry_after: Duration) -> Response {
+    let mut response = (
+        StatusCode::TOO_MANY_REQUESTS,
+        Json(BaseResponse {
+            success: false,
+            message: Some("Too many requests".to_string()),
+        }),
+    )
+        .into_response();
+    // round up, so clients don't retry too early
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(seconds.max(1)));
+    response
+}
+
+pub fn client_ip(headers: &HeaderMap, addr: SocketAddr) -> IpAddr {
+    if *TRUST_PROXY {
+        let forwarded = headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .collect::<Vec<&str>>();
+        // the entry added by the outermost trusted proxy
+        let ip = forwarded
+            .len()
+            .checked_sub(*TRUSTED_PROXY_HOPS)
+            .filter(|_| *TRUSTED_PROXY_HOPS > 0)
+            .and_then(|i| forwarded[i].trim().parse().ok());
+        if let Some(ip) = ip {
+            return ip;
+        }
+    }
+    addr.ip()
+}
+
+// Rate limits the game api per ip and per user token and blocks ips probing for game ids
+pub async fn rate_limit(
+    State(state): State<SharedAppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Path(params): Path<HashMap<String, String>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let ip = client_ip(request.headers(), addr);
+    if let Some(remaining) = PROBE_GUARD.blocked_for(&ip) {
+        return too_many_requests(remaining);
+    }
+    if let Err(retry_after) = IP_LIMITER.check(&ip.to_string()) {
+        return too_many_requests(retry_after);
+    }
+    let game_exists = params
+        .get("game_id")
+        .is_some_and(|id| state.read().unwrap().contains_key(id));
+    if !game_exists {
+        PROBE_GUARD.record(ip);
+    }
+
+    // the user token is sent in the query or in the json body
+    let (parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, MAX_BODY_SIZE).await {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            return (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("Request body too large".to_string()),
+                }),
+            )
+                .into_response();
+        }
+    };
+    let token = Query::<TokenQuery>::try_from_uri(&parts.uri)
+        .ok()
+        .and_then(|q| q.0.token)
+        .or_else(|| {
+            serde_json::from_slice::<TokenQuery>(&bytes)
+                .ok()
+                .and_then(|q| q.token)
+        });
+    if let Some(token) = token {
+        if let Err(retry_after) = TOKEN_LIMITER.check(&token) {
+            return too_many_requests(retry_after);
+        }
+    }
+
+    next.run(Request::from_parts(parts, Body::from(bytes)))
+        .await
+}
@@ -0,0 +1,91 @@
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+// Characters that are not rendered but can be used to make texts look alike, the zero-width
+// joiner is kept as it is part of emoji sequences
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00AD}'
+            | '\u{200B}'
+            | '\u{200C}'
+            | '\u{200E}'
+            | '\u{200F}'
+            | '\u{202A}'..='\u{202E}'
+            | '\u{2060}'..='\u{2064}'
+            | '\u{2066}'..='\u{2069}'
+            | '\u{FEFF}'
+    )
+}
+
+// Cleans up user input: strips control and invisible characters, normalizes to NFC,
+// collapses whitespace to single spaces and trims the result
+pub fn sanitize_text(input: &str) -> String {
+    let stripped: String = input
+        .chars()
+        .filter(|c| !is_invisible(*c))
+        .map(|c| if c.is_whitespace() { ' ' } else { c })
+        .filter(|c| !c.is_control())
+        .collect();
+    let normalized: String = stripped.nfc().collect();
+    normalized
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+// Length of a text as perceived by users, an emoji counts as one character
+pub fn grapheme_count(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collapses_whitespace_and_strips_control_characters() {
+        assert_eq!(sanitize_text("  a \t b\n\nc\u{0007} "), "a b c");
+    }
+
+    #[test]
+    fn composes_combining_characters() {
+        // "e" followed by a combining acute accent becomes a single "é"
+        let sanitized = sanitize_text("caf\u{0065}\u{0301}");
+        assert_eq!(sanitized, "caf\u{00E9}");
+        assert_eq!(sanitized.chars().count(), 4);
+    }
+
+    #[test]
+    fn strips_bidi_overrides() {
+        assert_eq!(sanitize_text("\u{202E}txt.exe\u{202C}"), "txt.exe");
+        assert_eq!(sanitize_text("a\u{2067}b\u{2069}c\u{200F}"), "abc");
+    }
+
+    #[test]
+    fn strips_zero_width_characters_but_keeps_emoji_joiners() {
+        assert_eq!(sanitize_text("f\u{200B}u\u{FEFF}n"), "fun");
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        assert_eq!(sanitize_text(family), family);
+    }
+
+    #[test]
+    fn counts_graphemes() {
+        assert_eq!(grapheme_count("abc"), 3);
+        // combining characters that can't be composed still count once
+        assert_eq!(grapheme_count("q\u{0307}\u{0323}"), 1);
+        assert_eq!(
+            grapheme_count("\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}"),
+            1
+        );
+        assert_eq!(grapheme_count("\u{1F1E9}\u{1F1EA}"), 1);
+        assert_eq!(grapheme_count(""), 0);
+    }
+
+    #[test]
+    fn counts_graphemes_of_right_to_left_text() {
+        assert_eq!(grapheme_count("\u{05E9}\u{05DC}\u{05D5}\u{05DD}"), 4);
+        // a hebrew letter with a vowel point is one grapheme
+        assert_eq!(grapheme_count("\u{05E9}\u{05B8}"), 1);
+    }
+}
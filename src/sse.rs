@@ -0,0 +1,181 @@
+use crate::connections::{Connection, Resume};
+use crate::dto::{BaseResponse, TokenQuery};
+use crate::game::user_connected;
+use crate::websocket::{disconnect, record_lag, snapshot_message, METRICS};
+use crate::ws_dto::LobbyEvent;
+use crate::SharedAppState;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::IntoResponse;
+use axum::Json;
+use futures::stream;
+use std::convert::Infallible;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tracing::{event, Level};
+
+// Comments are sent this often to keep idle streams open behind proxies
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+// Url: /api/{game_id}/events?token=<user token>
+// Streams the lobby events as server-sent events, for clients that can't open a websocket
+// Method: GET
+// Response: text/event-stream of LobbyEvent
+#[utoipa::path(
+    get,
+    path = "/api/{game_id}/events",
+    params(
+        ("game_id" = String, Path, description = "ID of the game"),
+        ("token" = String, Query, description = "User token"),
+        ("Last-Event-ID" = Option<u64>, Header, description = "Sequence number of the last event received, sent by browsers when they reconnect")
+    ),
+    responses(
+        (status = 200, description = "Stream of lobby events", content_type = "text/event-stream", body = LobbyEvent),
+        (status = 400, description = "Token is required", body = BaseResponse),
+        (status = 404, description = "Game not found | User not found", body = BaseResponse)
+    ),
+    description = "Streams the same messages as the websocket, without the replies to actions. The id \
+    of each lobby event is its sequence number, so clients resume after the last event they received \
+    when they reconnect. If the missed events are not buffered anymore or the client falls behind, a \
+    `snapshot` message is sent instead. Comments are sent regularly to keep the stream open."
+)]
+pub async fn events_handler(
+    State(state): State<SharedAppState>,
+    Path(game_id): Path<String>,
+    Query(query): Query<TokenQuery>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let Some(token) = query.token else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(BaseResponse {
+                success: false,
+                message: Some("Token is required".to_string()),
+            })
+            .into_response(),
+        );
+    };
+    let last_seq = headers
+        .get("last-event-id")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok());
+
+    // Register the stream like a websocket connection, clients that resume get the events
+    // they missed or a snapshot
+    let connection = {
+        let read_state = state.read().unwrap();
+        let Some(lobby) = read_state.get(&game_id) else {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("Game not found".to_string()),
+                })
+                .into_response(),
+            );
+        };
+        let lobby = lobby.read().unwrap();
+        if !lobby.users.read().unwrap().iter().any(|u| u.token == token) {
+            return (
+                StatusCode::NOT_FOUND,
+                Json(BaseResponse {
+                    success: false,
+                    message: Some("User not found".to_string()),
+                })
+                .into_response(),
+            );
+        }
+        let connection = lobby.game.connections.connect(&token, last_seq);
+        let missed = match &connection.resume {
+            Resume::Events(events) => events.iter().map(|e| (e.message.clone(), e.seq)).collect(),
+            Resume::Snapshot => vec![snapshot_message(&lobby, &token)],
+        };
+        user_connected(&lobby, &token);
+        (connection, missed)
+    };
+    event!(Level::INFO, "User {} opened an event stream", token);
+    METRICS.connections.fetch_add(1, Ordering::Relaxed);
+
+    // The stream is fed by a task, which ends once the client closed the stream
+    let (tx, rx) = mpsc::channel::<Event>(1);
+    tokio::spawn(stream_events(state, game_id, token, connection, tx));
+    let events = stream::unfold(rx, |mut rx| async move {
+        rx.recv()
+            .await
+            .map(|event| (Ok::<_, Infallible>(event), rx))
+    });
+    (
+        StatusCode::OK,
+        Sse::new(events)
+            .keep_alive(KeepAlive::new().interval(KEEP_ALIVE_INTERVAL))
+            .into_response(),
+    )
+}
+
+async fn stream_events(
+    state: SharedAppState,
+    game_id: String,
+    token: String,
+    (connection, missed): (Connection, Vec<(String, u64)>),
+    tx: mpsc::Sender<Event>,
+) {
+    let Connection {
+        id,
+        events: mut rx,
+        mut direct,
+        resume,
+    } = connection;
+    // events up to this sequence number are contained in the snapshot that was sent
+    let mut seen = match resume {
+        Resume::Snapshot => missed.last().map_or(0, |(_, seq)| *seq),
+        Resume::Events(_) => 0,
+    };
+    for (message, seq) in missed {
+        if tx
+            .send(Event::default().id(seq.to_string()).data(message))
+            .await
+            .is_err()
+        {
+            break;
+        }
+    }
+    while !tx.is_closed() {
+        let event = tokio::select! {
+            event = rx.recv() => match event {
+                Ok(event) if event.except.as_ref() == Some(&token) => continue,
+                Ok(event) if event.seq <= seen => continue,
+                Ok(event) => Event::default().id(event.seq.to_string()).data(event.message),
+                Err(RecvError::Lagged(skipped)) => {
+                    record_lag(&token, skipped);
+                    rx = rx.resubscribe();
+                    let snapshot = state
+                        .read()
+                        .unwrap()
+                        .get(&game_id)
+                        .map(|lobby| snapshot_message(&lobby.read().unwrap(), &token));
+                    let Some((snapshot, seq)) = snapshot else {
+                        break;
+                    };
+                    seen = seq;
+                    Event::default().id(seq.to_string()).data(snapshot)
+                }
+                Err(RecvError::Closed) => break,
+            },
+            // messages to the user only have no id, they are not replayed
+            msg = direct.recv() => match msg {
+                Some(msg) => Event::default().data(msg),
+                // the user was removed from the game
+                None => break,
+            },
+            _ = tx.closed() => break,
+        };
+        if tx.send(event).await.is_err() {
+            break;
+        }
+    }
+    METRICS.connections.fetch_sub(1, Ordering::Relaxed);
+    disconnect(&state, &game_id, &token, id);
+}
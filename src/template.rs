@@ -0,0 +1,232 @@
+use crate::dto::{GameDTO, ValidationErrorDTO};
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+
+// Parses a text with inline placeholders like `You find {{a noun}}.` into the text sections
+// around the placeholders and the hints inside of them
+pub fn parse_placeholders(text: &str) -> Result<(Vec<String>, Vec<String>), String> {
+    let mut sections = vec![];
+    let mut hints = vec![];
+    let mut rest = text;
+    while let Some(start) = rest.find(OPEN) {
+        let before = &rest[..start];
+        if before.contains(CLOSE) {
+            return Err(format!("Unexpected '{}' without opening '{}'", CLOSE, OPEN));
+        }
+        let after = &rest[start + OPEN.len()..];
+        let end = after
+            .find(CLOSE)
+            .ok_or(format!("Placeholder '{}' is never closed", OPEN))?;
+        let hint = &after[..end];
+        if hint.contains(OPEN) {
+            return Err("Placeholders must not be nested".to_string());
+        }
+        sections.push(before.trim().to_string());
+        hints.push(hint.trim().to_string());
+        rest = &after[end + CLOSE.len()..];
+    }
+    if rest.contains(CLOSE) {
+        return Err(format!("Unexpected '{}' without opening '{}'", CLOSE, OPEN));
+    }
+    sections.push(rest.trim().to_string());
+    Ok((sections, hints))
+}
+
+// Replaces the text sections and hints of a game with the parsed placeholder text, if one is given
+pub fn expand_placeholders(game: GameDTO) -> Result<GameDTO, Vec<ValidationErrorDTO>> {
+    match &game.text {
+        None => Ok(game),
+        Some(text) => match parse_placeholders(text) {
+            Ok((text_section, hints)) => Ok(GameDTO {
+                text: None,
+                text_section,
+                hints,
+                ..game
+            }),
+            Err(message) => Err(vec![ValidationErrorDTO {
+                field: "text".to_string(),
+                message,
+            }]),
+        },
+    }
+}
+
+// Common English words that are never blanked when blanking every Nth word
+const STOP_WORDS: [&str; 120] = [
+    "a", "about", "above", "after", "again", "against", "all", "am", "an", "and", "any", "are",
+    "as", "at", "be", "because", "been", "before", "being", "below", "between", "both", "but",
+    "by", "can", "could", "did", "do", "does", "doing", "down", "during", "each", "few", "for",
+    "from", "further", "had", "has", "have", "having", "he", "her", "here", "hers", "herself",
+    "him", "himself", "his", "how", "i", "if", "in", "into", "is", "it", "its", "itself", "just",
+    "me", "more", "most", "my", "myself", "no", "nor", "not", "now", "of", "off", "on", "once",
+    "only", "or", "other", "our", "ours", "out", "over", "own", "same", "she", "should", "so",
+    "some", "such", "than", "that", "the", "their", "them", "then", "there", "these", "they",
+    "this", "those", "through", "to", "too", "under", "until", "up", "very", "was", "we", "were",
+    "what", "when", "where", "which", "while", "who", "whom", "why", "will", "with", "would",
+    "you", "your",
+];
+
+pub const DEFAULT_MARKER: &str = "___";
+pub const DEFAULT_EVERY: usize = 5;
+
+// How the words to blank are chosen when generating a template from prose
+pub enum BlankMode<'a> {
+    Markers(&'a str), // must not be empty, the text would be split between all characters
+    Words(&'a [String]),
+    EveryNth(usize),
+}
+
+// Splits a word into leading punctuation, the word itself and trailing punctuation
+fn split_punctuation(token: &str) -> (&str, &str, &str) {
+    let core_start = token
+        .find(|c: char| c.is_alphanumeric())
+        .unwrap_or(token.len());
+    let core_end = token
+        .rfind(|c: char| c.is_alphanumeric())
+        .map(|i| i + token[i..].chars().next().unwrap().len_utf8())
+        .unwrap_or(core_start);
+    (
+        &token[..core_start],
+        &token[core_start..core_end],
+        &token[core_end..],
+    )
+}
+
+// Creates draft text sections from a plain text by blanking words, returns the sections and the
+// blanked words in order
+pub fn generate_sections(text: &str, mode: &BlankMode) -> (Vec<String>, Vec<String>) {
+    if let BlankMode::Markers(marker) = mode {
+        let sections = text.split(marker).map(|s| s.trim().to_string()).collect();
+        return (sections, vec![]);
+    }
+
+    let mut sections = vec![];
+    let mut blanked = vec![];
+    let mut current = String::new();
+    let mut content_words = 0;
+    let mut previous_blanked = false;
+    for token in text.split_whitespace() {
+        let (prefix, word, suffix) = split_punctuation(token);
+        let lower = word.to_lowercase();
+        let is_content_word = !word.is_empty()
+            && word
+                .chars()
+                .all(|c| c.is_alphabetic() || c == '\'' || c == '-')
+            && !STOP_WORDS.contains(&lower.as_str());
+        let blank = match mode {
+            BlankMode::Words(words) => {
+                !word.is_empty() && words.iter().any(|w| w.trim().to_lowercase() == lower)
+            }
+            BlankMode::EveryNth(n) => {
+                if is_content_word {
+                    content_words += 1;
+                }
+                is_content_word && content_words % (*n).max(1) == 0
+            }
+            BlankMode::Markers(_) => false,
+        };
+        // two gaps next to each other would leave an empty section between them
+        if blank && !previous_blanked {
+            current.push(' ');
+            current.push_str(prefix);
+            sections.push(current.trim().to_string());
+            blanked.push(word.to_string());
+            current = suffix.to_string();
+            previous_blanked = true;
+        } else {
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(token);
+            previous_blanked = false;
+        }
+    }
+    sections.push(current.trim().to_string());
+    (sections, blanked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dto::GameMetadataDTO;
+
+    fn game(text: &str) -> GameDTO {
+        GameDTO {
+            name: "Game".to_string(),
+            text: Some(text.to_string()),
+            text_section: vec!["old".to_string()],
+            hints: vec![],
+            constraints: vec![],
+            metadata: GameMetadataDTO::default(),
+        }
+    }
+
+    #[test]
+    fn parses_sections_and_hints() {
+        let (sections, hints) = parse_placeholders("You find {{ a noun }}. It is {{}}").unwrap();
+        assert_eq!(sections, vec!["You find", ". It is", ""]);
+        assert_eq!(hints, vec!["a noun", ""]);
+    }
+
+    #[test]
+    fn text_without_placeholders_is_one_section() {
+        let (sections, hints) = parse_placeholders("  Just text ").unwrap();
+        assert_eq!(sections, vec!["Just text"]);
+        assert!(hints.is_empty());
+    }
+
+    #[test]
+    fn rejects_nested_placeholders() {
+        assert_eq!(
+            parse_placeholders("A {{noun {{adjective}} }} here"),
+            Err("Placeholders must not be nested".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_unterminated_placeholders() {
+        assert_eq!(
+            parse_placeholders("A {{noun"),
+            Err("Placeholder '{{' is never closed".to_string())
+        );
+        assert_eq!(
+            parse_placeholders("A {{noun}} and {{verb"),
+            Err("Placeholder '{{' is never closed".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_closing_without_opening() {
+        let expected = Err("Unexpected '}}' without opening '{{'".to_string());
+        assert_eq!(parse_placeholders("A noun}} here"), expected);
+        assert_eq!(parse_placeholders("A }} {{noun}}"), expected);
+        assert_eq!(parse_placeholders("A {{noun}} }}"), expected);
+    }
+
+    #[test]
+    fn expands_text_into_sections_and_hints() {
+        let expanded = expand_placeholders(game("A {{noun}} b")).unwrap();
+        assert_eq!(expanded.text, None);
+        assert_eq!(expanded.text_section, vec!["A", "b"]);
+        assert_eq!(expanded.hints, vec!["noun"]);
+    }
+
+    #[test]
+    fn keeps_sections_without_text() {
+        let expanded = expand_placeholders(GameDTO {
+            text: None,
+            ..game("")
+        })
+        .unwrap();
+        assert_eq!(expanded.text_section, vec!["old"]);
+    }
+
+    #[test]
+    fn reports_invalid_text_as_validation_error() {
+        let errors = expand_placeholders(game("A {{noun {{x}} }}")).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "text");
+        assert_eq!(errors[0].message, "Placeholders must not be nested");
+    }
+}
@@ -1,9 +1,30 @@
+use crate::dto::SectionDiffDTO;
 use rand::Rng;
 use std::iter;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub fn id_generator(len: usize) -> String {
     const CHARSET: &[u8] = b"ABCDEFGHIJKLMNPQRSTUVWXYZ0123456789";
     let mut rng = rand::thread_rng();
     let one_char = || CHARSET[rng.gen_range(0..CHARSET.len())] as char;
     iter::repeat_with(one_char).take(len).collect()
-}
\ No newline at end of file
+}
+
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Compares two lists of text sections index by index and returns the changed ones
+pub fn diff_sections(from: &[String], to: &[String]) -> Vec<SectionDiffDTO> {
+    (0..from.len().max(to.len()))
+        .filter(|&i| from.get(i) != to.get(i))
+        .map(|i| SectionDiffDTO {
+            index: i as u32,
+            from: from.get(i).cloned(),
+            to: to.get(i).cloned(),
+        })
+        .collect()
+}
@@ -0,0 +1,728 @@
+use crate::auth::SCOPES;
+use crate::dto::{
+    AnswerType, CreateApiKeyDTO, GameDTO, GameMetadataDTO, GapConstraintDTO, LobbySettings,
+    ValidationErrorDTO, WordListDTO,
+};
+use crate::sanitize::grapheme_count;
+
+pub const MAX_NAME_LENGTH: usize = 64;
+pub const MAX_SECTIONS: usize = 51;
+pub const MAX_SECTION_LENGTH: usize = 500;
+pub const MAX_TOTAL_LENGTH: usize = 5000;
+pub const MIN_GAPS: usize = 1;
+pub const MAX_HINT_LENGTH: usize = 64;
+pub const MAX_ANSWER_LENGTH: usize = 140;
+pub const MAX_DESCRIPTION_LENGTH: usize = 1000;
+pub const MAX_TAGS: usize = 20;
+pub const MAX_TAG_LENGTH: usize = 32;
+pub const MAX_AUTHOR_LENGTH: usize = 64;
+pub const MAX_AGE_RATING: u8 = 21;
+pub const MAX_PLAYERS: usize = 100;
+pub const MAX_PLAYER_NAME_LENGTH: usize = 32;
+pub const MIN_TIME_LIMIT: u64 = 10;
+pub const MAX_TIME_LIMIT: u64 = 3600;
+pub const MAX_GUESS_DELAY: u64 = 60;
+pub const MAX_POINTS: u32 = 100;
+pub const MAX_BLOCKED_WORDS: usize = 1000;
+pub const MAX_BLOCKED_WORD_LENGTH: usize = 64;
+pub const MIN_KEY_LIFETIME: u64 = 60;
+
+fn error(field: &str, message: String) -> ValidationErrorDTO {
+    ValidationErrorDTO {
+        field: field.to_string(),
+        message,
+    }
+}
+
+// Validates a game template and returns all problems found, an empty list means the template is valid
+pub fn validate_game(game: &GameDTO) -> Vec<ValidationErrorDTO> {
+    let mut errors = vec![];
+
+    // name format
+    let name_length = game.name.chars().count();
+    if game.name.trim().is_empty() {
+        errors.push(error("name", "Name must not be empty".to_string()));
+    } else {
+        if name_length > MAX_NAME_LENGTH {
+            errors.push(error(
+                "name",
+                format!("Name must be at most {} characters long", MAX_NAME_LENGTH),
+            ));
+        }
+        if game.name.trim() != game.name {
+            errors.push(error(
+                "name",
+                "Name must not start or end with whitespace".to_string(),
+            ));
+        }
+        if !game
+            .name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == ' ' || c == '-' || c == '_')
+        {
+            errors.push(error(
+                "name",
+                "Name may only contain letters, digits, spaces, '-' and '_'".to_string(),
+            ));
+        }
+    }
+
+    // text sections
+    let sections = &game.text_section;
+    if sections.is_empty() {
+        errors.push(error(
+            "text_section",
+            "Text sections must not be empty".to_string(),
+        ));
+        return errors;
+    }
+    if sections.len() > MAX_SECTIONS {
+        errors.push(error(
+            "text_section",
+            format!("At most {} text sections are allowed", MAX_SECTIONS),
+        ));
+    }
+    // every section except the last one is followed by a gap
+    if sections.len() - 1 < MIN_GAPS {
+        errors.push(error(
+            "text_section",
+            format!(
+                "At least {} text sections are required to create {} gap(s)",
+                MIN_GAPS + 1,
+                MIN_GAPS
+            ),
+        ));
+    }
+    let last = sections.len() - 1;
+    for (i, section) in sections.iter().enumerate() {
+        let field = format!("text_section[{}]", i);
+        // the first and last section may be empty if the text starts or ends with a gap
+        if i != 0 && i != last && section.trim().is_empty() {
+            errors.push(error(&field, "Text section must not be blank".to_string()));
+        }
+        if section.chars().count() > MAX_SECTION_LENGTH {
+            errors.push(error(
+                &field,
+                format!(
+                    "Text section must be at most {} characters long",
+                    MAX_SECTION_LENGTH
+                ),
+            ));
+        }
+        // the placeholder tokens would be misread when the template is exported as text
+        if section.contains("{{") || section.contains("}}") {
+            errors.push(error(
+                &field,
+                "Text section must not contain '{{' or '}}'".to_string(),
+            ));
+        }
+    }
+    // hints are optional, but if given there has to be one per gap
+    if !game.hints.is_empty() && game.hints.len() != sections.len() - 1 {
+        errors.push(error(
+            "hints",
+            format!(
+                "Expected {} hints, one per gap, but got {}",
+                sections.len() - 1,
+                game.hints.len()
+            ),
+        ));
+    }
+    for (i, hint) in game.hints.iter().enumerate() {
+        if hint.chars().count() > MAX_HINT_LENGTH {
+            errors.push(error(
+                &format!("hints[{}]", i),
+                format!("Hint must be at most {} characters long", MAX_HINT_LENGTH),
+            ));
+        }
+        // a hint is exported inside a placeholder, where a single brace already ends it early
+        if hint.contains(['{', '}']) {
+            errors.push(error(
+                &format!("hints[{}]", i),
+                "Hint must not contain '{' or '}'".to_string(),
+            ));
+        }
+    }
+    // constraints are optional as well, but if given there has to be one per gap
+    if !game.constraints.is_empty() && game.constraints.len() != sections.len() - 1 {
+        errors.push(error(
+            "constraints",
+            format!(
+                "Expected {} constraints, one per gap, but got {}",
+                sections.len() - 1,
+                game.constraints.len()
+            ),
+        ));
+    }
+    for (i, constraint) in game.constraints.iter().enumerate() {
+        let field = format!("constraints[{}]", i);
+        let min = constraint.min_length.unwrap_or(0);
+        let max = constraint.max_length.unwrap_or(MAX_ANSWER_LENGTH);
+        if max == 0 || max > MAX_ANSWER_LENGTH {
+            errors.push(error(
+                &field,
+                format!("Maximum length must be between 1 and {}", MAX_ANSWER_LENGTH),
+            ));
+        }
+        if min > max {
+            errors.push(error(
+                &field,
+                "Minimum length must not be greater than the maximum length".to_string(),
+            ));
+        }
+    }
+    let total_length: usize = sections.iter().map(|s| s.chars().count()).sum();
+    if total_length > MAX_TOTAL_LENGTH {
+        errors.push(error(
+            "text_section",
+            format!(
+                "Text sections must be at most {} characters long in total",
+                MAX_TOTAL_LENGTH
+            ),
+        ));
+    }
+
+    validate_metadata(&game.metadata, &mut errors);
+
+    errors
+}
+
+// Checks a sanitized answer against the constraint of its gap and the answer length of the lobby,
+// lengths are counted in graphemes
+pub fn validate_answer(
+    content: &str,
+    constraint: &GapConstraintDTO,
+    answer_length: usize,
+) -> Vec<ValidationErrorDTO> {
+    let mut errors = vec![];
+    let length = grapheme_count(content);
+    if length == 0 {
+        if constraint.required {
+            errors.push(error("content", "An answer is required".to_string()));
+        }
+        return errors;
+    }
+    if let Some(min) = constraint.min_length {
+        if length < min {
+            errors.push(error(
+                "content",
+                format!("Answer must be at least {} characters long", min),
+            ));
+        }
+    }
+    let max = constraint
+        .max_length
+        .map_or(answer_length, |max| max.min(answer_length));
+    if length > max {
+        errors.push(error(
+            "content",
+            format!("Answer must be at most {} characters long", max),
+        ));
+    }
+    if constraint.answer_type == AnswerType::SingleWord && content.split_whitespace().count() > 1 {
+        errors.push(error("content", "Answer must be a single word".to_string()));
+    }
+    errors
+}
+
+// Checks a sanitized player name, the length is counted in graphemes
+pub fn validate_player_name(name: &str) -> Vec<ValidationErrorDTO> {
+    let mut errors = vec![];
+    let length = grapheme_count(name);
+    if length == 0 {
+        errors.push(error("name", "Name must not be empty".to_string()));
+    } else if length > MAX_PLAYER_NAME_LENGTH {
+        errors.push(error(
+            "name",
+            format!(
+                "Name must be at most {} characters long",
+                MAX_PLAYER_NAME_LENGTH
+            ),
+        ));
+    }
+    errors
+}
+
+// Validates the settings of a lobby
+pub fn validate_settings(settings: &LobbySettings) -> Vec<ValidationErrorDTO> {
+    let mut errors = vec![];
+    if let Some(max_players) = settings.max_players {
+        if max_players == 0 || max_players > MAX_PLAYERS {
+            errors.push(error(
+                "settings.max_players",
+                format!("Maximum players must be between 1 and {}", MAX_PLAYERS),
+            ));
+        }
+    }
+    if settings.answer_length == 0 || settings.answer_length > MAX_ANSWER_LENGTH {
+        errors.push(error(
+            "settings.answer_length",
+            format!("Answer length must be between 1 and {}", MAX_ANSWER_LENGTH),
+        ));
+    }
+    for (field, limit) in [
+        ("settings.fill_time_limit", settings.fill_time_limit),
+        ("settings.guess_time_limit", settings.guess_time_limit),
+        (
+            "settings.offline_grace_period",
+            settings.offline_grace_period,
+        ),
+    ] {
+        if limit.is_some_and(|l| !(MIN_TIME_LIMIT..=MAX_TIME_LIMIT).contains(&l)) {
+            errors.push(error(
+                field,
+                format!(
+                    "Time limit must be between {} and {} seconds",
+                    MIN_TIME_LIMIT, MAX_TIME_LIMIT
+                ),
+            ));
+        }
+    }
+    if settings.guess_delay > MAX_GUESS_DELAY {
+        errors.push(error(
+            "settings.guess_delay",
+            format!("Guess delay must be at most {} seconds", MAX_GUESS_DELAY),
+        ));
+    }
+    for (field, points) in [
+        (
+            "settings.points_per_correct_guess",
+            settings.points_per_correct_guess,
+        ),
+        (
+            "settings.points_per_fooled_user",
+            settings.points_per_fooled_user,
+        ),
+    ] {
+        if points > MAX_POINTS {
+            errors.push(error(
+                field,
+                format!("Points must be at most {}", MAX_POINTS),
+            ));
+        }
+    }
+    errors
+}
+
+// Validates a word list uploaded by an admin
+pub fn validate_word_list(list: &WordListDTO) -> Vec<ValidationErrorDTO> {
+    let mut errors = vec![];
+    if !is_language_code(&list.language) {
+        errors.push(error(
+            "language",
+            "Language must be a language code such as \"en\" or \"de-AT\"".to_string(),
+        ));
+    }
+    if list.words.len() > MAX_BLOCKED_WORDS {
+        errors.push(error(
+            "words",
+            format!("At most {} words are allowed", MAX_BLOCKED_WORDS),
+        ));
+    }
+    for (i, word) in list.words.iter().enumerate() {
+        let length = grapheme_count(word.trim());
+        if length == 0 || length > MAX_BLOCKED_WORD_LENGTH {
+            errors.push(error(
+                &format!("words[{}]", i),
+                format!(
+                    "Word must be between 1 and {} characters long",
+                    MAX_BLOCKED_WORD_LENGTH
+                ),
+            ));
+        }
+    }
+    errors
+}
+
+// Validates a request for a new api key
+pub fn validate_api_key(key: &CreateApiKeyDTO) -> Vec<ValidationErrorDTO> {
+    let mut errors = vec![];
+    let name_length = key.name.trim().chars().count();
+    if name_length == 0 || name_length > MAX_NAME_LENGTH {
+        errors.push(error(
+            "name",
+            format!(
+                "Name must be between 1 and {} characters long",
+                MAX_NAME_LENGTH
+            ),
+        ));
+    }
+    if key.scopes.is_empty() {
+        errors.push(error(
+            "scopes",
+            "At least one scope is required".to_string(),
+        ));
+    }
+    for (i, scope) in key.scopes.iter().enumerate() {
+        if !SCOPES.contains(&scope.as_str()) {
+            errors.push(error(
+                &format!("scopes[{}]", i),
+                format!("Scope must be one of {}", SCOPES.join(", ")),
+            ));
+        }
+    }
+    if key.expires_in.is_some_and(|e| e < MIN_KEY_LIFETIME) {
+        errors.push(error(
+            "expires_in",
+            format!(
+                "Keys must be valid for at least {} seconds",
+                MIN_KEY_LIFETIME
+            ),
+        ));
+    }
+    errors
+}
+
+// Language codes like "en", "deu" or "de-AT"
+fn is_language_code(language: &str) -> bool {
+    let mut parts = language.split('-');
+    let primary = parts.next().unwrap_or("");
+    let region = parts.next();
+    (2..=3).contains(&primary.len())
+        && primary.chars().all(|c| c.is_ascii_lowercase())
+        && region.is_none_or(|r| {
+            (2..=4).contains(&r.len()) && r.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+        && parts.next().is_none()
+}
+
+fn validate_metadata(metadata: &GameMetadataDTO, errors: &mut Vec<ValidationErrorDTO>) {
+    if metadata
+        .description
+        .as_ref()
+        .is_some_and(|d| d.chars().count() > MAX_DESCRIPTION_LENGTH)
+    {
+        errors.push(error(
+            "description",
+            format!(
+                "Description must be at most {} characters long",
+                MAX_DESCRIPTION_LENGTH
+            ),
+        ));
+    }
+    if metadata.tags.len() > MAX_TAGS {
+        errors.push(error(
+            "tags",
+            format!("At most {} tags are allowed", MAX_TAGS),
+        ));
+    }
+    for (i, tag) in metadata.tags.iter().enumerate() {
+        let length = tag.trim().chars().count();
+        if length == 0 || length > MAX_TAG_LENGTH {
+            errors.push(error(
+                &format!("tags[{}]", i),
+                format!(
+                    "Tag must be between 1 and {} characters long",
+                    MAX_TAG_LENGTH
+                ),
+            ));
+        }
+    }
+    if metadata
+        .language
+        .as_ref()
+        .is_some_and(|l| !is_language_code(l))
+    {
+        errors.push(error(
+            "language",
+            "Language must be a language code such as \"en\" or \"de-AT\"".to_string(),
+        ));
+    }
+    if metadata
+        .author
+        .as_ref()
+        .is_some_and(|a| a.trim().is_empty() || a.chars().count() > MAX_AUTHOR_LENGTH)
+    {
+        errors.push(error(
+            "author",
+            format!(
+                "Author must be between 1 and {} characters long",
+                MAX_AUTHOR_LENGTH
+            ),
+        ));
+    }
+    if metadata.age_rating.is_some_and(|a| a > MAX_AGE_RATING) {
+        errors.push(error(
+            "age_rating",
+            format!("Age rating must be at most {}", MAX_AGE_RATING),
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game(sections: &[&str]) -> GameDTO {
+        GameDTO {
+            name: "Game".to_string(),
+            text: None,
+            text_section: sections.iter().map(|s| s.to_string()).collect(),
+            hints: vec![],
+            constraints: vec![],
+            metadata: GameMetadataDTO::default(),
+        }
+    }
+
+    fn fields(errors: &[ValidationErrorDTO]) -> Vec<&str> {
+        errors.iter().map(|e| e.field.as_str()).collect()
+    }
+
+    fn constraint(min: Option<usize>, max: Option<usize>) -> GapConstraintDTO {
+        GapConstraintDTO {
+            min_length: min,
+            max_length: max,
+            ..GapConstraintDTO::default()
+        }
+    }
+
+    #[test]
+    fn accepts_valid_game() {
+        let mut game = game(&["", "A", "b", ""]);
+        game.hints = vec!["noun".to_string(), "".to_string(), "verb".to_string()];
+        game.constraints = vec![GapConstraintDTO::default(); 3];
+        game.metadata.language = Some("de-AT".to_string());
+        assert!(validate_game(&game).is_empty());
+    }
+
+    #[test]
+    fn rejects_empty_template() {
+        let errors = validate_game(&game(&[]));
+        assert_eq!(fields(&errors), vec!["text_section"]);
+        assert_eq!(errors[0].message, "Text sections must not be empty");
+    }
+
+    #[test]
+    fn rejects_too_few_sections_for_a_gap() {
+        assert_eq!(fields(&validate_game(&game(&["A"]))), vec!["text_section"]);
+    }
+
+    #[test]
+    fn rejects_blank_sections_between_gaps() {
+        let errors = validate_game(&game(&["", "A", "  ", "b", ""]));
+        assert_eq!(fields(&errors), vec!["text_section[2]"]);
+        assert_eq!(errors[0].message, "Text section must not be blank");
+    }
+
+    #[test]
+    fn rejects_invalid_names() {
+        for name in ["", "  ", " Game", "Game ", "Game!", "Game/1"] {
+            let mut game = game(&["A", "b"]);
+            game.name = name.to_string();
+            assert_eq!(fields(&validate_game(&game)), vec!["name"], "{:?}", name);
+        }
+        let mut game = game(&["A", "b"]);
+        game.name = "Das Büro-Spiel_2".to_string();
+        assert!(validate_game(&game).is_empty());
+    }
+
+    #[test]
+    fn rejects_too_long_names() {
+        let mut game = game(&["A", "b"]);
+        game.name = "a".repeat(MAX_NAME_LENGTH);
+        assert!(validate_game(&game).is_empty());
+        game.name.push('a');
+        assert_eq!(fields(&validate_game(&game)), vec!["name"]);
+    }
+
+    #[test]
+    fn rejects_too_many_and_too_long_sections() {
+        let sections = vec!["a"; MAX_SECTIONS + 1];
+        assert_eq!(
+            fields(&validate_game(&game(&sections))),
+            vec!["text_section"]
+        );
+
+        let long = "a".repeat(MAX_SECTION_LENGTH + 1);
+        let errors = validate_game(&game(&["A", &long]));
+        assert_eq!(fields(&errors), vec!["text_section[1]"]);
+
+        let section = "a".repeat(MAX_SECTION_LENGTH);
+        let sections = vec![section.as_str(); MAX_TOTAL_LENGTH / MAX_SECTION_LENGTH + 1];
+        assert_eq!(
+            fields(&validate_game(&game(&sections))),
+            vec!["text_section"]
+        );
+    }
+
+    #[test]
+    fn rejects_hints_not_matching_the_gaps() {
+        let mut game = game(&["A", "b", "c"]);
+        game.hints = vec!["noun".to_string()];
+        assert_eq!(fields(&validate_game(&game)), vec!["hints"]);
+        game.hints = vec!["noun".to_string(), "a".repeat(MAX_HINT_LENGTH + 1)];
+        assert_eq!(fields(&validate_game(&game)), vec!["hints[1]"]);
+    }
+
+    #[test]
+    fn rejects_placeholder_braces() {
+        let mut game = game(&["A {{", "b"]);
+        game.hints = vec!["a}".to_string()];
+        assert_eq!(
+            fields(&validate_game(&game)),
+            vec!["text_section[0]", "hints[0]"]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_constraints() {
+        let mut game = game(&["A", "b", "c"]);
+        game.constraints = vec![GapConstraintDTO::default()];
+        assert_eq!(fields(&validate_game(&game)), vec!["constraints"]);
+
+        game.constraints = vec![constraint(None, Some(0)), constraint(None, None)];
+        assert_eq!(fields(&validate_game(&game)), vec!["constraints[0]"]);
+
+        game.constraints = vec![
+            constraint(None, Some(MAX_ANSWER_LENGTH + 1)),
+            constraint(Some(5), Some(4)),
+        ];
+        let errors = validate_game(&game);
+        assert_eq!(fields(&errors), vec!["constraints[0]", "constraints[1]"]);
+        assert_eq!(
+            errors[1].message,
+            "Minimum length must not be greater than the maximum length"
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_metadata() {
+        let mut game = game(&["A", "b"]);
+        game.metadata = GameMetadataDTO {
+            description: Some("a".repeat(MAX_DESCRIPTION_LENGTH + 1)),
+            tags: vec!["".to_string()],
+            language: Some("EN".to_string()),
+            author: Some(" ".to_string()),
+            age_rating: Some(MAX_AGE_RATING + 1),
+        };
+        assert_eq!(
+            fields(&validate_game(&game)),
+            vec!["description", "tags[0]", "language", "author", "age_rating"]
+        );
+    }
+
+    #[test]
+    fn validates_answers_against_the_constraint() {
+        let required = GapConstraintDTO::default();
+        assert_eq!(
+            fields(&validate_answer("", &required, 140)),
+            vec!["content"]
+        );
+        let optional = GapConstraintDTO {
+            required: false,
+            ..GapConstraintDTO::default()
+        };
+        assert!(validate_answer("", &optional, 140).is_empty());
+
+        let constraint = constraint(Some(3), Some(5));
+        assert_eq!(validate_answer("ab", &constraint, 140).len(), 1);
+        assert!(validate_answer("abc", &constraint, 140).is_empty());
+        assert_eq!(validate_answer("abcdef", &constraint, 140).len(), 1);
+        // the answer length of the lobby applies if it is shorter than the constraint
+        assert_eq!(
+            validate_answer("abcd", &constraint, 3)[0].message,
+            "Answer must be at most 3 characters long"
+        );
+    }
+
+    #[test]
+    fn validates_single_words() {
+        let constraint = GapConstraintDTO {
+            answer_type: AnswerType::SingleWord,
+            ..GapConstraintDTO::default()
+        };
+        assert!(validate_answer("word", &constraint, 140).is_empty());
+        assert_eq!(validate_answer("two words", &constraint, 140).len(), 1);
+    }
+
+    #[test]
+    fn counts_answer_length_in_graphemes() {
+        let constraint = constraint(None, Some(2));
+        assert!(validate_answer("👍🏽e\u{301}", &constraint, 140).is_empty());
+    }
+
+    #[test]
+    fn validates_player_names() {
+        assert!(validate_player_name("Alice").is_empty());
+        assert_eq!(fields(&validate_player_name("")), vec!["name"]);
+        let long = "a".repeat(MAX_PLAYER_NAME_LENGTH + 1);
+        assert_eq!(fields(&validate_player_name(&long)), vec!["name"]);
+        let emojis = "👨‍👩‍👧".repeat(MAX_PLAYER_NAME_LENGTH);
+        assert!(validate_player_name(&emojis).is_empty());
+    }
+
+    #[test]
+    fn accepts_default_settings() {
+        assert!(validate_settings(&LobbySettings::default()).is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        let settings = LobbySettings {
+            max_players: Some(0),
+            answer_length: MAX_ANSWER_LENGTH + 1,
+            fill_time_limit: Some(MIN_TIME_LIMIT - 1),
+            guess_time_limit: Some(MAX_TIME_LIMIT + 1),
+            offline_grace_period: Some(MIN_TIME_LIMIT),
+            guess_delay: MAX_GUESS_DELAY + 1,
+            points_per_correct_guess: MAX_POINTS + 1,
+            ..LobbySettings::default()
+        };
+        assert_eq!(
+            fields(&validate_settings(&settings)),
+            vec![
+                "settings.max_players",
+                "settings.answer_length",
+                "settings.fill_time_limit",
+                "settings.guess_time_limit",
+                "settings.guess_delay",
+                "settings.points_per_correct_guess",
+            ]
+        );
+    }
+
+    #[test]
+    fn validates_api_keys() {
+        let key = CreateApiKeyDTO {
+            name: "ci".to_string(),
+            scopes: vec!["templates:read".to_string()],
+            expires_in: Some(MIN_KEY_LIFETIME),
+        };
+        assert!(validate_api_key(&key).is_empty());
+
+        let key = CreateApiKeyDTO {
+            name: " ".to_string(),
+            scopes: vec![],
+            expires_in: Some(MIN_KEY_LIFETIME - 1),
+        };
+        assert_eq!(
+            fields(&validate_api_key(&key)),
+            vec!["name", "scopes", "expires_in"]
+        );
+
+        let key = CreateApiKeyDTO {
+            name: "ci".to_string(),
+            scopes: vec!["keys:manage".to_string(), "admin".to_string()],
+            expires_in: None,
+        };
+        assert_eq!(fields(&validate_api_key(&key)), vec!["scopes[1]"]);
+    }
+
+    #[test]
+    fn validates_word_lists() {
+        let list = WordListDTO {
+            language: "de-AT".to_string(),
+            words: vec!["wort".to_string()],
+        };
+        assert!(validate_word_list(&list).is_empty());
+
+        let list = WordListDTO {
+            language: "german".to_string(),
+            words: vec!["wort".to_string(), " ".to_string()],
+        };
+        assert_eq!(
+            fields(&validate_word_list(&list)),
+            vec!["language", "words[1]"]
+        );
+    }
+}
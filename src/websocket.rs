@@ -1,11 +1,29 @@
+use crate::connections::{Connection, Resume, CHANNEL_CAPACITY};
+use crate::dto::WebsocketMetricsDTO;
+use crate::game::{
+    claim_gap, fill_gap, guess, set_ready, unclaim_gap, user_connected, user_disconnected,
+};
+use crate::objects::Lobby;
+use crate::ws_dto::{
+    Authenticated, ClientMessage, ServerMessage, WSAck, WSApiDoc, WSError, MIN_PROTOCOL_VERSION,
+    PROTOCOL_VERSION,
+};
+use crate::SharedAppState;
+use axum::extract::ws::{close_code, CloseFrame, Message, WebSocket};
 use axum::extract::{Path, State, WebSocketUpgrade};
-use axum::extract::ws::{Message, WebSocket};
+use axum::http::StatusCode;
 use axum::response::IntoResponse;
+use axum::Json;
 use futures::{SinkExt, StreamExt};
-use serde_json::from_str;
+use lazy_static::lazy_static;
+use serde_json::{from_str, json, to_string, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tokio::time::{interval, timeout};
 use tracing::{event, Level};
-use crate::SharedAppState;
-use crate::ws_dto::WSAuthMessage;
+use utoipa::OpenApi;
 
 pub async fn websocket_handler(
     ws: WebSocketUpgrade,
@@ -15,79 +33,284 @@ pub async fn websocket_handler(
     ws.on_upgrade(|socket| websocket(socket, game_id, state))
 }
 
+// Clients have to send an auth message within this time after connecting
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+lazy_static! {
+    // Pings keep idle connections open behind proxies and are answered by clients with a pong
+    static ref PING_INTERVAL: Duration = Duration::from_secs(
+        std::env::var("WS_PING_INTERVAL")
+            .ok()
+            .and_then(|seconds| seconds.parse().ok())
+            .filter(|seconds| *seconds > 0)
+            .unwrap_or(30)
+    );
+    // Connections that send nothing, not even a pong, for this long are closed, it has to be
+    // longer than the ping interval so clients have the chance to answer a ping
+    static ref IDLE_TIMEOUT: Duration = std::env::var("WS_IDLE_TIMEOUT")
+        .ok()
+        .and_then(|seconds| seconds.parse().ok())
+        .map(Duration::from_secs)
+        .filter(|timeout| *timeout > *PING_INTERVAL)
+        .unwrap_or((*PING_INTERVAL * 3).max(Duration::from_secs(90)));
+    pub static ref METRICS: Metrics = Metrics::default();
+}
+
+// Counters of all websocket and event stream connections since the server was started
+#[derive(Default)]
+pub struct Metrics {
+    pub connections: AtomicU64, // currently open
+    pub lag_events: AtomicU64,  // times a connection fell behind the lobby events
+    pub skipped_events: AtomicU64,
+    pub resyncs: AtomicU64, // snapshots sent after lagging or resuming
+    pub idle_timeouts: AtomicU64,
+}
+
+pub fn record_lag(token: &str, skipped: u64) {
+    event!(
+        Level::WARN,
+        "User {} lagged behind by {} events",
+        token,
+        skipped
+    );
+    METRICS.lag_events.fetch_add(1, Ordering::Relaxed);
+    METRICS.skipped_events.fetch_add(skipped, Ordering::Relaxed);
+}
+
+pub fn metrics() -> WebsocketMetricsDTO {
+    WebsocketMetricsDTO {
+        connections: METRICS.connections.load(Ordering::Relaxed),
+        lag_events: METRICS.lag_events.load(Ordering::Relaxed),
+        skipped_events: METRICS.skipped_events.load(Ordering::Relaxed),
+        resyncs: METRICS.resyncs.load(Ordering::Relaxed),
+        idle_timeouts: METRICS.idle_timeouts.load(Ordering::Relaxed),
+        channel_capacity: *CHANNEL_CAPACITY,
+        ping_interval: PING_INTERVAL.as_secs(),
+        idle_timeout: IDLE_TIMEOUT.as_secs(),
+    }
+}
+
+// Close codes in the range reserved for applications
+const CLOSE_UNAUTHORIZED: u16 = 4001;
+const CLOSE_REMOVED: u16 = 4003;
+const CLOSE_GAME_NOT_FOUND: u16 = 4004;
+const CLOSE_AUTH_TIMEOUT: u16 = 4008;
+const CLOSE_IDLE_TIMEOUT: u16 = 4009;
+const CLOSE_UNSUPPORTED_VERSION: u16 = 4010;
+
+async fn close(mut stream: WebSocket, code: u16, reason: &str) {
+    event!(Level::INFO, "Closing websocket: {}", reason);
+    let _ = stream
+        .send(Message::Close(Some(CloseFrame {
+            code,
+            reason: reason.to_string().into(),
+        })))
+        .await;
+}
+
+// Waits for the auth message of the client and returns the user it belongs to together with
+// the protocol version used for the connection and the last event the client has seen
+async fn authenticate(
+    stream: &mut WebSocket,
+    state: &SharedAppState,
+    game_id: &str,
+) -> Result<(Authenticated, Option<u64>), (u16, &'static str)> {
+    let msg = loop {
+        match timeout(AUTH_TIMEOUT, stream.recv()).await {
+            Err(_) => return Err((CLOSE_AUTH_TIMEOUT, "Authentication timed out")),
+            Ok(None) | Ok(Some(Err(_))) => return Err((close_code::AWAY, "Connection closed")),
+            Ok(Some(Ok(Message::Text(msg)))) => break msg,
+            Ok(Some(Ok(Message::Close(_)))) => return Err((close_code::AWAY, "Connection closed")),
+            // pings are answered by axum, binary messages are not part of the protocol
+            Ok(Some(Ok(_))) => continue,
+        }
+    };
+    let (token, version, last_seq) = match from_str::<ClientMessage>(&msg) {
+        Ok(ClientMessage::Auth {
+            token,
+            version,
+            last_seq,
+        }) => (token, version, last_seq),
+        _ => return Err((close_code::POLICY, "Expected auth message")),
+    };
+    // newer clients talk to older servers with the version of the server
+    let version = version
+        .unwrap_or(MIN_PROTOCOL_VERSION)
+        .min(PROTOCOL_VERSION);
+    if version < MIN_PROTOCOL_VERSION {
+        return Err((CLOSE_UNSUPPORTED_VERSION, "Unsupported protocol version"));
+    }
+    let state = state.read().unwrap();
+    let lobby = match state.get(game_id) {
+        Some(lobby) => lobby.read().unwrap(),
+        None => return Err((CLOSE_GAME_NOT_FOUND, "Game not found")),
+    };
+    let users = lobby.users.read().unwrap();
+    match users.iter().find(|u| u.token == token) {
+        Some(user) => Ok((
+            Authenticated {
+                id: user.id.clone(),
+                name: user.name.clone(),
+                token: user.token.clone(),
+                team: user.team.clone(),
+                version,
+            },
+            last_seq,
+        )),
+        None => Err((CLOSE_UNAUTHORIZED, "User not in game")),
+    }
+}
+
 // This function deals with a single websocket connection, i.e., a single
-// connected client / user, for which we will spawn two independent tasks (for
-// receiving / sending chat messages).
+// connected client / user. The client has to authenticate as a player of the
+// game first, only then we subscribe to the game's events and spawn two
+// independent tasks (for receiving actions / sending events and replies).
 async fn websocket(
-    stream: WebSocket,
+    mut stream: WebSocket,
     Path(game_id): Path<String>,
     State(state): State<SharedAppState>,
 ) {
     // check if the game exists
     if !state.read().unwrap().contains_key(&game_id) {
+        close(stream, CLOSE_GAME_NOT_FOUND, "Game not found").await;
         return;
     }
-    // By splitting, we can send and receive at the same time.
-    let (mut sender, mut receiver) = stream.split();
 
-    // Subscribe to the broadcast channel for the game
-    let mut rx = {
-        let state = state.read().unwrap();
-        let lobby = state.get(&game_id).unwrap();
-        let read_lobby = lobby.read().unwrap();
-        read_lobby.game.tx.subscribe()
+    // the connection is bound to this user until it is closed
+    let (user, last_seq) = match authenticate(&mut stream, &state, &game_id).await {
+        Ok(auth) => auth,
+        Err((code, reason)) => {
+            close(stream, code, reason).await;
+            return;
+        }
     };
+    event!(
+        Level::INFO,
+        "User {} joined WScom with protocol version {}",
+        user.token,
+        user.version
+    );
+
+    // Register the connection with the lobby, the lobby may have been closed meanwhile.
+    // Clients that resume get the events they missed, or a snapshot of the lobby if they
+    // are not buffered anymore.
+    let token = user.token.clone();
+    let connection = state.read().unwrap().get(&game_id).map(|lobby| {
+        let lobby = lobby.read().unwrap();
+        let connection = lobby.game.connections.connect(&token, last_seq);
+        let (missed, seen) = match &connection.resume {
+            Resume::Events(events) => (events.iter().map(|e| e.message.clone()).collect(), 0),
+            Resume::Snapshot => {
+                let (snapshot, seq) = snapshot_message(&lobby, &token);
+                (vec![snapshot], seq)
+            }
+        };
+        user_connected(&lobby, &token);
+        (connection, missed, seen)
+    });
+    let Some((
+        Connection {
+            id,
+            events: mut rx,
+            mut direct,
+            ..
+        },
+        missed,
+        mut seen,
+    )) = connection
+    else {
+        close(stream, CLOSE_GAME_NOT_FOUND, "Game not found").await;
+        return;
+    };
+    METRICS.connections.fetch_add(1, Ordering::Relaxed);
+    let authenticated = to_string(&ServerMessage::Authenticated(user)).unwrap();
+    for message in std::iter::once(authenticated).chain(missed) {
+        if stream.send(Message::Text(message)).await.is_err() {
+            METRICS.connections.fetch_sub(1, Ordering::Relaxed);
+            disconnect(&state, &game_id, &token, id);
+            return;
+        }
+    }
+
+    // By splitting, we can send and receive at the same time.
+    let (mut sender, mut receiver) = stream.split();
+    // Replies to the actions of this client, lobby events go through the broadcast channel
+    let (reply_tx, mut reply_rx) = mpsc::unbounded_channel::<Message>();
 
     // Spawn a task to send messages to the client
+    let send_state = state.clone();
+    let send_game_id = game_id.clone();
+    let send_token = token.clone();
     let mut send_task = tokio::spawn(async move {
-        while let Ok(msg) = rx.recv().await {
-            if sender.send(Message::Text(msg)).await.is_err() {
+        let mut ping = interval(*PING_INTERVAL);
+        ping.tick().await; // the first tick completes immediately
+        loop {
+            let msg = tokio::select! {
+                event = rx.recv() => match event {
+                    Ok(event) if event.except.as_ref() == Some(&send_token) => continue,
+                    // already contained in the snapshot
+                    Ok(event) if event.seq <= seen => continue,
+                    Ok(event) => Message::Text(event.message),
+                    Err(RecvError::Lagged(skipped)) => {
+                        // the client is too slow, the skipped events are replaced by a snapshot
+                        record_lag(&send_token, skipped);
+                        // the events still queued are contained in the snapshot as well
+                        rx = rx.resubscribe();
+                        let snapshot = send_state
+                            .read()
+                            .unwrap()
+                            .get(&send_game_id)
+                            .map(|lobby| snapshot_message(&lobby.read().unwrap(), &send_token));
+                        let Some((snapshot, seq)) = snapshot else {
+                            break;
+                        };
+                        seen = seq;
+                        Message::Text(snapshot)
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                msg = direct.recv() => match msg {
+                    Some(msg) => Message::Text(msg),
+                    // the user was removed from the game
+                    None => Message::Close(Some(CloseFrame {
+                        code: CLOSE_REMOVED,
+                        reason: "Removed from game".into(),
+                    })),
+                },
+                Some(reply) = reply_rx.recv() => reply,
+                _ = ping.tick() => Message::Ping(vec![]),
+            };
+            let closing = matches!(msg, Message::Close(_));
+            if sender.send(msg).await.is_err() || closing {
                 break;
             }
         }
     });
 
     // Spawn a task to receive messages from the client
+    let recv_state = state.clone();
+    let recv_game_id = game_id.clone();
+    let recv_token = token.clone();
     let mut recv_task = tokio::spawn(async move {
-        while let Some(msg) = receiver.next().await {
-            let msg = match msg {
-                Ok(msg) => msg,
-                Err(_) => break,
-            };
-            let msg = match msg {
-                Message::Text(msg) => msg,
-                _ => continue,
+        loop {
+            let msg = match timeout(*IDLE_TIMEOUT, receiver.next()).await {
+                Ok(Some(Ok(Message::Text(msg)))) => msg,
+                Ok(Some(Ok(Message::Close(_)) | Err(_))) | Ok(None) => break,
+                // pongs only keep the connection alive
+                Ok(Some(Ok(_))) => continue,
+                Err(_) => {
+                    event!(Level::INFO, "User {} timed out", recv_token);
+                    METRICS.idle_timeouts.fetch_add(1, Ordering::Relaxed);
+                    // the send task closes the connection, the client may still answer it
+                    let _ = reply_tx.send(Message::Close(Some(CloseFrame {
+                        code: CLOSE_IDLE_TIMEOUT,
+                        reason: "Idle timeout".into(),
+                    })));
+                    continue;
+                }
             };
-
-            let auth_msg = from_str::<WSAuthMessage>(&msg);
-            if auth_msg.is_err() {
-                event!(Level::ERROR, "{}", auth_msg.unwrap_err());
-                break;
-            }
-            let auth_msg = auth_msg.unwrap();
-            if auth_msg.obj != "auth" {
-                event!(Level::ERROR, "Expected auth message, got {}", auth_msg.obj);
-                break;
-            }
-            // check if the user is in the game
-            if state
-                .read()
-                .unwrap()
-                .get(&game_id)
-                .unwrap()
-                .read()
-                .unwrap()
-                .users
-                .read()
-                .unwrap()
-                .iter()
-                .filter(|u| u.token == auth_msg.token)
-                .count()
-                == 1
-            {
-                event!(Level::INFO, "User {} joined WScom", auth_msg.token);
-            } else {
-                event!(Level::ERROR, "User not in game");
+            let reply = handle_action(&recv_state, &recv_game_id, &recv_token, &msg);
+            if reply_tx.send(Message::Text(reply)).is_err() {
                 break;
             }
         }
@@ -98,4 +321,115 @@ async fn websocket(
         _ = &mut send_task => recv_task.abort(),
         _ = &mut recv_task => send_task.abort(),
     }
+    METRICS.connections.fetch_sub(1, Ordering::Relaxed);
+    disconnect(&state, &game_id, &token, id);
+}
+
+// Snapshot of the lobby for the user, serialized, and the sequence number it was taken at
+pub fn snapshot_message(lobby: &Lobby, token: &str) -> (String, u64) {
+    METRICS.resyncs.fetch_add(1, Ordering::Relaxed);
+    let snapshot = lobby.snapshot(token);
+    let seq = snapshot.seq;
+    (to_string(&ServerMessage::Snapshot(snapshot)).unwrap(), seq)
+}
+
+pub fn disconnect(state: &SharedAppState, game_id: &str, token: &str, id: u64) {
+    if let Some(lobby) = state.read().unwrap().get(game_id) {
+        let lobby = lobby.read().unwrap();
+        lobby.game.connections.disconnect(token, id);
+        user_disconnected(state, game_id, &lobby, token);
+    }
+}
+
+// Runs a player action of an authenticated client and returns the ack or error reply
+fn handle_action(state: &SharedAppState, game_id: &str, token: &str, msg: &str) -> String {
+    let message = match from_str::<ClientMessage>(msg) {
+        Ok(message) => message,
+        Err(err) => {
+            // answer with the request id if the message has one, even if it is not valid
+            let request_id = from_str::<Value>(msg)
+                .ok()
+                .and_then(|v| v.get("request_id")?.as_str().map(|id| id.to_string()));
+            return error_reply(
+                request_id,
+                StatusCode::BAD_REQUEST,
+                format!("Invalid message: {}", err),
+            );
+        }
+    };
+    let result = match &message {
+        ClientMessage::Auth { .. } => {
+            return error_reply(
+                None,
+                StatusCode::BAD_REQUEST,
+                "Already authenticated".to_string(),
+            );
+        }
+        ClientMessage::Claim { gap_id, .. } => claim_gap(state, game_id, token, *gap_id),
+        ClientMessage::Unclaim { gap_id, .. } => unclaim_gap(state, game_id, token, *gap_id),
+        ClientMessage::Fill {
+            gap_id, content, ..
+        } => fill_gap(state, game_id, token, *gap_id, content),
+        ClientMessage::Guess { guesses, .. } => guess(state, game_id, token, guesses),
+        ClientMessage::Ready { ready, .. } => set_ready(state, game_id, token, *ready),
+    };
+    let request_id = message.request_id().map(|id| id.to_string());
+    let reply = match result {
+        Ok(()) => ServerMessage::Ack(WSAck {
+            request_id: request_id.unwrap_or_default(),
+        }),
+        Err(err) => ServerMessage::Error(WSError {
+            request_id,
+            status: err.status.as_u16(),
+            message: err.message,
+            errors: err.errors,
+        }),
+    };
+    to_string(&reply).unwrap()
+}
+
+fn error_reply(request_id: Option<String>, status: StatusCode, message: String) -> String {
+    to_string(&ServerMessage::Error(WSError {
+        request_id,
+        status: status.as_u16(),
+        message,
+        errors: vec![],
+    }))
+    .unwrap()
+}
+
+// Url: /websocket/schema
+// JSON Schema of all websocket messages, so clients can generate their types
+// Method: GET
+// Response: JSON Schema with the messages in $defs
+#[utoipa::path(
+    get,
+    path = "/websocket/schema",
+    responses(
+        (status = 200, description = "JSON Schema of the websocket messages")
+    ),
+    description = "JSON Schema (draft 2020-12) of the websocket messages. `ServerMessage` are \
+    sent by the server, `ClientMessage` by the client, both are defined in `$defs`. \
+    `protocol_version` is the latest protocol version of the server."
+)]
+pub async fn schema_handler() -> impl IntoResponse {
+    let schemas = WSApiDoc::openapi()
+        .components
+        .map(|c| c.schemas)
+        .unwrap_or_default();
+    // the schemas reference each other as OpenAPI components, JSON Schema uses $defs
+    let defs = to_string(&schemas)
+        .unwrap()
+        .replace("#/components/schemas/", "#/$defs/");
+    Json(json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "WordWeaver websocket messages",
+        "protocol_version": PROTOCOL_VERSION,
+        "min_protocol_version": MIN_PROTOCOL_VERSION,
+        "oneOf": [
+            {"$ref": "#/$defs/ServerMessage"},
+            {"$ref": "#/$defs/ClientMessage"}
+        ],
+        "$defs": from_str::<Value>(&defs).unwrap(),
+    }))
 }
@@ -1,77 +1,176 @@
+use crate::dto::{CurrentGapTextDTO, GuessDTO, LobbySettings, UserDTO, ValidationErrorDTO};
 use serde::{Deserialize, Serialize};
-use utoipa::ToSchema;
+use utoipa::{OpenApi, ToSchema};
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct WSMessage<T> {
-    pub obj: String,
-    pub value: T,
+// Version of the websocket protocol spoken by the server, clients send the version they
+// speak in the auth message and the server answers with the version used for the connection
+pub const PROTOCOL_VERSION: u32 = 1;
+// Oldest version the server still speaks, clients without a version are assumed to speak it
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+// Messages sent by the server as {"obj": "<type>", "value": <payload>}
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[serde(tag = "obj", content = "value", rename_all = "snake_case")]
+pub enum ServerMessage {
+    // Replies to the messages of a single client
+    Authenticated(Authenticated),
+    Ack(WSAck),
+    Error(WSError),
+    Snapshot(Snapshot), // instead of the missed events if they are not buffered anymore
+    // Lobby events sent to all users
+    UserJoined(TempUser),
+    TeamJoined(TempUser),
+    UserReady(UserReady),
+    SettingsChanged(LobbySettings),
+    ChangeView(String),
+    GapClaimed(u32),
+    GapUnclaimed(u32),
+    TeamClaimed(TeamClaim),
+    GapFilled(u32),
+    AnswerReturned(u32),
+    StartGuessing(u64),  // seconds until guessing starts
+    UserLeft(String),    // public id of the user that left or was removed
+    UserOnline(String),  // public id of the user that connected
+    UserOffline(String), // public id of the user whose last connection was closed
+    GuessScores(Vec<GuessScore>),
+    TeamScores(Vec<TeamScore>),
+    // Messages sent to a single user
+    GapResults(Vec<GapResult>), // how the answers of the user were guessed
+    ClaimExpired(u32),          // gap the user claimed but did not fill in time
+    Kicked(String),             // reason
+}
+
+// Messages sent by clients as {"obj": "<type>", ...fields}. The first message has to be auth,
+// the actions are answered with an ack or error message with the same request id and are
+// done as the user the connection is bound to.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+#[serde(tag = "obj", rename_all = "snake_case")]
+pub enum ClientMessage {
+    Auth {
+        token: String,
+        version: Option<u32>,
+        last_seq: Option<u64>, // resumes after the event with this sequence number
+    },
+    Claim {
+        request_id: String,
+        gap_id: u32,
+    },
+    Unclaim {
+        request_id: String,
+        gap_id: u32,
+    },
+    Fill {
+        request_id: String,
+        gap_id: u32,
+        content: String,
+    },
+    Guess {
+        request_id: String,
+        guesses: Vec<GuessDTO>,
+    },
+    Ready {
+        request_id: String,
+        ready: bool,
+    },
+}
+
+impl ClientMessage {
+    pub fn request_id(&self) -> Option<&str> {
+        match self {
+            ClientMessage::Auth { .. } => None,
+            ClientMessage::Claim { request_id, .. }
+            | ClientMessage::Unclaim { request_id, .. }
+            | ClientMessage::Fill { request_id, .. }
+            | ClientMessage::Guess { request_id, .. }
+            | ClientMessage::Ready { request_id, .. } => Some(request_id),
+        }
+    }
+}
+
+// Lobby event as sent to clients, events are numbered per lobby so clients can resume after
+// reconnecting. Replies and messages to a single user are sent without a sequence number.
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct LobbyEvent {
+    #[serde(flatten)]
+    pub message: ServerMessage,
+    pub seq: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct Snapshot {
+    pub seq: u64, // events up to this sequence number are contained in the snapshot
+    pub view: String,
+    pub current_gap_text: Vec<CurrentGapTextDTO>,
+    pub users: Vec<UserDTO>,
+    pub teams: Option<Vec<String>>,
+    pub settings: LobbySettings,
+    pub scores: Vec<GuessScore>, // empty until the ranking is shown
+    pub team_scores: Vec<TeamScore>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct TempUser {
+    pub id: String,
+    pub name: String,
+    pub team: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct Authenticated {
+    pub id: String, // public id of the user, used in the lobby events
     pub name: String,
     pub token: String,
+    pub team: Option<String>,
+    pub version: u32, // protocol version used for the connection
 }
 
 #[derive(Serialize, Deserialize, Debug, ToSchema)]
 pub struct GuessScore {
+    pub id: String,
     pub name: String,
-    pub token: String,
     pub score: u32,
 }
 
-#[derive(Deserialize, Debug)]
-pub struct WSAuthMessage {
-    pub obj: String,
-    pub token: String,
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct TeamScore {
+    pub team: String,
+    pub members: Vec<String>,
+    pub score: u32,
 }
 
-impl WSMessage<TempUser> {
-    pub fn user_joined(name: String, token: String) -> Self {
-        WSMessage {
-            obj: "user_joined".to_string(),
-            value: TempUser { name, token },
-        }
-    }
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct GapResult {
+    pub gap_id: u32,
+    pub correct_guesses: u32,
+    pub wrong_guesses: u32,
 }
 
-impl WSMessage<Vec<GuessScore>> {
-    pub fn guess_scores(guesses: Vec<GuessScore>) -> Self {
-        WSMessage {
-            obj: "guess_scores".to_string(),
-            value: guesses,
-        }
-    }
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct TeamClaim {
+    pub gap_id: u32,
+    pub team: String,
 }
 
-impl<String> WSMessage<String> {
-    pub fn change_view(view: String) -> Self {
-        WSMessage {
-            obj: "change_view".to_string(),
-            value: view,
-        }
-    }
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct UserReady {
+    pub id: String,
+    pub ready: bool,
 }
 
-impl<U32> WSMessage<U32> {
-    pub fn gap_claimed(gap_id: U32) -> Self {
-        WSMessage {
-            obj: "gap_claimed".to_string(),
-            value: gap_id,
-        }
-    }
-
-    pub fn gap_filled(gap_id: U32) -> Self {
-        WSMessage {
-            obj: "gap_filled".to_string(),
-            value: gap_id,
-        }
-    }
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct WSAck {
+    pub request_id: String,
+}
 
-    pub fn start_guessing(delay: U32) -> Self {
-        WSMessage {
-            obj: "start_guessing".to_string(),
-            value: delay,
-        }
-    }
+#[derive(Serialize, Deserialize, Debug, ToSchema)]
+pub struct WSError {
+    pub request_id: Option<String>, // None if the request could not be read
+    pub status: u16,
+    pub message: String,
+    pub errors: Vec<ValidationErrorDTO>,
 }
+
+// Schemas of all websocket messages, served as JSON Schema by /websocket/schema
+#[derive(OpenApi)]
+#[openapi(components(schemas(ServerMessage, LobbyEvent, ClientMessage)))]
+pub struct WSApiDoc;